@@ -12,6 +12,7 @@ use bevy::{
     window::{PresentMode, WindowResolution},
     winit::{UpdateMode, WinitSettings},
 };
+use protocol::{BenchResult, FrameMetrics, HistogramBucket, LoadMetrics, PROTOCOL_VERSION};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
@@ -29,6 +30,13 @@ struct BevyCounter {
     pub color: Color,
 }
 
+/// When startup milestones were reached, in ms since the page's time origin.
+#[derive(Resource, Default)]
+struct LoadTimes {
+    startup_ms: Option<f64>,
+    first_frame_ms: Option<f64>,
+}
+
 #[derive(Component)]
 struct Bird {
     velocity: Vec3,
@@ -36,6 +44,12 @@ struct Bird {
 
 const FIXED_TIMESTEP: f32 = 0.2;
 
+const MEASUREMENT_SECS: f32 = 5.;
+/// Frames taking longer than this (in ms) are counted as over budget.
+const FRAME_BUDGET_MS: f32 = 1000. / 60.;
+/// Upper bounds (in ms) of the frame time histogram buckets. A final bucket holds the rest.
+const HISTOGRAM_BUCKETS_MS: [f32; 9] = [4., 8., 12., 16.7, 20., 25., 33.3, 50., 100.];
+
 fn main() {
     App::new()
         .add_plugins((
@@ -59,7 +73,10 @@ fn main() {
             count: 0,
             color: Color::WHITE,
         })
+        .init_resource::<LoadTimes>()
         .add_systems(Startup, setup)
+        .add_systems(PostStartup, record_startup)
+        .add_systems(First, record_first_frame)
         .add_systems(FixedUpdate, scheduled_spawner)
         .add_systems(
             Update,
@@ -309,12 +326,39 @@ fn counter_system(
     };
 }
 
+fn record_startup(mut load_times: ResMut<LoadTimes>) {
+    load_times.startup_ms = page_time_ms();
+}
+
+/// Frame rendering happens at the end of each update, so the first frame is done by the time
+/// the second update begins.
+fn record_first_frame(frame_count: Res<FrameCount>, mut load_times: ResMut<LoadTimes>) {
+    if frame_count.0 == 1 && load_times.first_frame_ms.is_none() {
+        load_times.first_frame_ms = page_time_ms();
+    }
+}
+
+/// The current time in ms since the page's time origin, as given by `performance.now()`.
+#[cfg(target_arch = "wasm32")]
+fn page_time_ms() -> Option<f64> {
+    web_sys::window()?
+        .performance()
+        .map(|performance| performance.now())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn page_time_ms() -> Option<f64> {
+    None
+}
+
 fn measure(
     counter: ResMut<BevyCounter>,
+    load_times: Res<LoadTimes>,
     time: Res<Time>,
     frame_count: Res<FrameCount>,
     mut start_time: Local<Option<f32>>,
     mut start_frame: Local<Option<u32>>,
+    mut frame_times: Local<Vec<f32>>,
     mut done: Local<bool>,
 ) {
     if *done {
@@ -335,13 +379,71 @@ fn measure(
     let start_time = start_time.unwrap();
     let start_frame = start_frame.unwrap();
 
+    frame_times.push(time.delta_secs() * 1000.0);
+
     let elapsed = time.elapsed_secs() - start_time;
 
-    if elapsed >= 5. {
+    if elapsed >= MEASUREMENT_SECS {
         *done = true;
-        info!(
-            "Average Frame Time: {:.2}ms",
-            elapsed / (frame_count.0 as f32 - start_frame as f32) * 1000.0
-        );
+
+        let average_ms = elapsed / (frame_count.0 as f32 - start_frame as f32) * 1000.0;
+        info!("Average Frame Time: {:.2}ms", average_ms);
+
+        let mut sorted = frame_times.clone();
+        sorted.sort_by(f32::total_cmp);
+
+        let histogram = std::iter::once(0.)
+            .chain(HISTOGRAM_BUCKETS_MS)
+            .enumerate()
+            .map(|(i, lower_ms)| {
+                let upper_ms = HISTOGRAM_BUCKETS_MS.get(i).copied();
+                let count = sorted
+                    .iter()
+                    .filter(|t| **t >= lower_ms && upper_ms.is_none_or(|upper| **t < upper))
+                    .count();
+
+                HistogramBucket {
+                    lower_ms,
+                    upper_ms,
+                    count: count as u64,
+                }
+            })
+            .collect();
+
+        let result = BenchResult {
+            protocol_version: PROTOCOL_VERSION,
+            bench_version: env!("CARGO_PKG_VERSION").to_string(),
+            bird_count: counter.count,
+            measurement_start_secs: start_time as f64,
+            measurement_end_secs: time.elapsed_secs_f64(),
+            frames: FrameMetrics {
+                count: sorted.len() as u64,
+                average_ms,
+                p50_ms: percentile(&sorted, 50.),
+                p90_ms: percentile(&sorted, 90.),
+                p95_ms: percentile(&sorted, 95.),
+                p99_ms: percentile(&sorted, 99.),
+                max_ms: sorted.last().copied().unwrap_or_default(),
+                budget_ms: FRAME_BUDGET_MS,
+                over_budget: sorted.iter().filter(|t| **t > FRAME_BUDGET_MS).count() as u64,
+                histogram,
+            },
+            load: LoadMetrics {
+                startup_ms: load_times.startup_ms,
+                first_frame_ms: load_times.first_frame_ms,
+            },
+        };
+
+        info!("{}", result.to_message());
     }
 }
+
+/// Nearest-rank percentile of already sorted values.
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.;
+    }
+
+    let rank = (p / 100. * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -0,0 +1,186 @@
+//! The result message sent from the wasm bench to the runner.
+//!
+//! The bench logs a single line consisting of [`RESULT_PREFIX`] followed by a JSON encoded
+//! [`BenchResult`]. The runner picks that line out of the browser console, then parses and
+//! validates it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Incremented whenever [`BenchResult`] changes in a way that older runners can't read.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Marks the log line carrying the result.
+pub const RESULT_PREFIX: &str = "BENCH_RESULT ";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub protocol_version: u32,
+    /// Version of the bench crate that produced this result.
+    pub bench_version: String,
+    /// Number of birds on screen during the measurement.
+    pub bird_count: usize,
+    /// Start of the measurement window, in seconds since the app started.
+    pub measurement_start_secs: f64,
+    /// End of the measurement window, in seconds since the app started.
+    pub measurement_end_secs: f64,
+    pub frames: FrameMetrics,
+    pub load: LoadMetrics,
+}
+
+/// Points during startup, in ms since the page's time origin (roughly, navigation start).
+///
+/// These are `None` when the bench isn't running in a browser.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoadMetrics {
+    /// When the `Startup` schedule had finished running.
+    pub startup_ms: Option<f64>,
+    /// When the first frame had been rendered.
+    pub first_frame_ms: Option<f64>,
+}
+
+/// Frame time metrics over the measurement window. All times are in ms.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FrameMetrics {
+    /// Number of frames in the measurement window.
+    pub count: u64,
+    pub average_ms: f32,
+    pub p50_ms: f32,
+    pub p90_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+    pub budget_ms: f32,
+    /// Number of frames that took longer than `budget_ms`.
+    pub over_budget: u64,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistogramBucket {
+    /// Inclusive lower bound of the bucket.
+    pub lower_ms: f32,
+    /// Exclusive upper bound of the bucket, or `None` for the final, unbounded bucket.
+    pub upper_ms: Option<f32>,
+    pub count: u64,
+}
+
+impl HistogramBucket {
+    /// A short label for the bucket, like `<16.7`, or `>=100` for the final bucket.
+    pub fn label(&self) -> String {
+        match self.upper_ms {
+            Some(upper) => format!("<{upper}"),
+            None => format!(">={}", self.lower_ms),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Json(serde_json::Error),
+    Version { found: u32 },
+    Invalid(String),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "Malformed bench result: {e}"),
+            Self::Version { found } => write!(
+                f,
+                "Bench result has protocol version {found}, expected {PROTOCOL_VERSION}"
+            ),
+            Self::Invalid(reason) => write!(f, "Invalid bench result: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl BenchResult {
+    /// Encodes the result as a log line.
+    pub fn to_message(&self) -> String {
+        format!(
+            "{}{}",
+            RESULT_PREFIX,
+            serde_json::to_string(self).expect("BenchResult is always serializable")
+        )
+    }
+
+    /// Finds and parses a result in a log line. Returns `None` if the line doesn't carry one.
+    ///
+    /// Anything before the prefix or after the JSON object is ignored, since the browser
+    /// console may add a log level or styling.
+    pub fn from_message(text: &str) -> Option<Result<Self, ProtocolError>> {
+        let (_, json) = text.split_once(RESULT_PREFIX)?;
+
+        let result = serde_json::Deserializer::from_str(json)
+            .into_iter::<BenchResult>()
+            .next()?
+            .map_err(ProtocolError::Json)
+            .and_then(|result| result.validate().map(|_| result));
+
+        Some(result)
+    }
+
+    /// Checks that the result has the expected version and is internally consistent.
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        if self.protocol_version != PROTOCOL_VERSION {
+            return Err(ProtocolError::Version {
+                found: self.protocol_version,
+            });
+        }
+
+        let invalid = |reason: &str| Err(ProtocolError::Invalid(reason.to_string()));
+        let frames = &self.frames;
+
+        if self.measurement_end_secs < self.measurement_start_secs {
+            return invalid("measurement ends before it starts");
+        }
+        if frames.count == 0 {
+            return invalid("no frames were measured");
+        }
+
+        let times = [
+            frames.average_ms,
+            frames.p50_ms,
+            frames.p90_ms,
+            frames.p95_ms,
+            frames.p99_ms,
+            frames.max_ms,
+            frames.budget_ms,
+        ];
+        if times.iter().any(|t| !t.is_finite() || *t < 0.0) {
+            return invalid("frame times must be finite and non-negative");
+        }
+
+        let percentiles = [
+            frames.p50_ms,
+            frames.p90_ms,
+            frames.p95_ms,
+            frames.p99_ms,
+            frames.max_ms,
+        ];
+        if percentiles.windows(2).any(|pair| pair[0] > pair[1]) {
+            return invalid("frame time percentiles are out of order");
+        }
+
+        if frames.over_budget > frames.count {
+            return invalid("more frames over budget than frames measured");
+        }
+
+        if frames.histogram.iter().map(|b| b.count).sum::<u64>() != frames.count {
+            return invalid("histogram counts don't add up to the frame count");
+        }
+
+        if let (Some(startup), Some(first_frame)) = (self.load.startup_ms, self.load.first_frame_ms)
+        {
+            if first_frame < startup {
+                return invalid("first frame was rendered before startup finished");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,500 @@
+//! Estimates the effect of each option with linear models, like `analysis/analysis.py`.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+
+use crate::cli::AnalyzeArgs;
+use crate::options::Config;
+use crate::plot::{self, Series};
+use crate::regression::{self, Fit};
+use crate::report::{markdown_table, Objective};
+use crate::results::{self, Row};
+use crate::stats;
+
+/// The configuration columns, which are the categorical predictors.
+pub const FACTORS: [&str; 6] = [
+    "opt_level",
+    "wasm_opt",
+    "lto",
+    "codegen_units",
+    "strip",
+    "panic",
+];
+
+/// The level each factor's effects are measured against when not given with `--baseline`, the
+/// same as in `analysis.py`.
+const DEFAULT_BASELINES: [(&str, &str); 6] = [
+    ("opt_level", "Three"),
+    ("wasm_opt", "None"),
+    ("lto", "Off"),
+    ("codegen_units", "Default"),
+    ("strip", "None"),
+    ("panic", "Unwind"),
+];
+
+/// The level of a factor to compare its other levels against, like `opt_level=Three`.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    factor: String,
+    level: String,
+}
+
+impl Baseline {
+    pub fn parse(baseline: &str) -> Result<Baseline> {
+        let (factor, level) = baseline.split_once('=').with_context(|| {
+            format!("Expected <axis>=<value>, like opt_level=Three, found {baseline}")
+        })?;
+        let factor = factor.trim();
+
+        if !FACTORS.contains(&factor) {
+            anyhow::bail!(
+                "Unknown axis {factor}. Expected one of {}",
+                FACTORS.join(", ")
+            );
+        }
+
+        Ok(Baseline {
+            factor: factor.to_string(),
+            level: level.trim().to_string(),
+        })
+    }
+}
+
+/// A two-way interaction between two axes, like `opt_level:wasm_opt`.
+#[derive(Debug, Clone)]
+pub struct Interaction {
+    a: &'static str,
+    b: &'static str,
+}
+
+impl Interaction {
+    pub fn parse(interaction: &str) -> Result<Interaction> {
+        let (a, b) = interaction.split_once(':').with_context(|| {
+            format!("Expected <axis>:<axis>, like opt_level:wasm_opt, found {interaction}")
+        })?;
+
+        let factor = |name: &str| {
+            FACTORS
+                .into_iter()
+                .find(|factor| *factor == name.trim())
+                .with_context(|| {
+                    format!(
+                        "Unknown axis {}. Expected one of {}",
+                        name.trim(),
+                        FACTORS.join(", ")
+                    )
+                })
+        };
+        let (a, b) = (factor(a)?, factor(b)?);
+
+        if a == b {
+            anyhow::bail!("An interaction needs two different axes, found {interaction}");
+        }
+
+        Ok(Interaction { a, b })
+    }
+
+    fn name(&self) -> String {
+        format!("{}:{}", self.a, self.b)
+    }
+}
+
+/// Fits `response ~ C(opt_level) + C(wasm_opt) + ...` with treatment coding, plus any
+/// interactions, for each response and prints its coefficients. With interactions, also tests
+/// whether each one is significant and draws an interaction plot of it.
+pub fn analyze(args: &AnalyzeArgs) -> Result<()> {
+    let rows = results::read_results(&args.input)?;
+    if rows.is_empty() {
+        anyhow::bail!("{} has no results", args.input.display());
+    }
+
+    for response in &args.responses {
+        let rows = rows
+            .iter()
+            .filter(|row| response.value(row).is_some())
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            anyhow::bail!("No configurations have a value for {}", response.name());
+        }
+
+        let model = fit(&rows, response, &args.baselines, &args.interactions)
+            .with_context(|| format!("Fitting {}", response.name()))?;
+        print_fit(&model);
+
+        if !args.interactions.is_empty() {
+            println!();
+            print_interaction_tests(&rows, response, args, &model)?;
+
+            std::fs::create_dir_all(&args.plots)
+                .with_context(|| format!("Creating {}", args.plots.display()))?;
+            for interaction in &args.interactions {
+                let path = args.plots.join(format!(
+                    "{}-{}-{}.svg",
+                    response.name(),
+                    interaction.a,
+                    interaction.b
+                ));
+                std::fs::write(&path, interaction_plot(&rows, response, interaction))
+                    .with_context(|| format!("Writing {}", path.display()))?;
+            }
+            println!(
+                "Interaction plots for {} are in {}.",
+                response.name(),
+                args.plots.display()
+            );
+        }
+
+        println!();
+    }
+
+    println!("Signif. codes: 0 '***' 0.001 '**' 0.01 '*' 0.05 '.' 0.1 ' ' 1");
+
+    Ok(())
+}
+
+/// A fit along with the formula it was fitted with.
+struct Model {
+    formula: String,
+    fit: Fit,
+    /// Interaction terms left out because no configuration has that combination of values.
+    missing: Vec<String>,
+    /// Factors left out because every configuration has the same value of them.
+    constant: Vec<&'static str>,
+}
+
+fn fit(
+    rows: &[&Row],
+    response: &Objective,
+    baselines: &[Baseline],
+    interactions: &[Interaction],
+) -> Result<Model> {
+    // Code each factor as one indicator column per level other than the baseline, and each
+    // interaction as the product of those for both of its factors.
+
+    let mut terms = vec!["Intercept".to_string()];
+    let mut formula_terms = vec![];
+    // Each predictor is 1 when a configuration has all of the listed factor levels, and 0
+    // otherwise. The intercept lists none.
+    let mut predictors: Vec<Vec<(&str, String)>> = vec![vec![]];
+    let mut missing = vec![];
+    let mut constant = vec![];
+
+    let mut factors = HashMap::new();
+    for factor in FACTORS {
+        let (baseline, levels) = factor_levels(rows, factor, baselines)?;
+        if levels.len() == 1 {
+            constant.push(factor);
+            factors.insert(factor, (baseline, levels));
+            continue;
+        }
+        formula_terms.push(format!("C({factor}, Treatment('{baseline}'))"));
+
+        for level in levels.iter().filter(|level| **level != baseline) {
+            terms.push(format!("{factor} {level}"));
+            predictors.push(vec![(factor, level.clone())]);
+        }
+
+        factors.insert(factor, (baseline, levels));
+    }
+
+    for interaction in interactions {
+        let (a_baseline, a_levels) = &factors[interaction.a];
+        let (b_baseline, b_levels) = &factors[interaction.b];
+        if a_levels.len() == 1 || b_levels.len() == 1 {
+            continue;
+        }
+        formula_terms.push(format!(
+            "C({}, Treatment('{a_baseline}')):C({}, Treatment('{b_baseline}'))",
+            interaction.a, interaction.b
+        ));
+
+        for a_level in a_levels.iter().filter(|level| *level != a_baseline) {
+            for b_level in b_levels.iter().filter(|level| *level != b_baseline) {
+                let term = format!("{} {a_level}:{} {b_level}", interaction.a, interaction.b);
+                let predictor = vec![
+                    (interaction.a, a_level.clone()),
+                    (interaction.b, b_level.clone()),
+                ];
+
+                if rows.iter().any(|row| has_levels(&row.config, &predictor)) {
+                    terms.push(term);
+                    predictors.push(predictor);
+                } else {
+                    missing.push(term);
+                }
+            }
+        }
+    }
+
+    let x = rows
+        .iter()
+        .map(|row| {
+            predictors
+                .iter()
+                .map(|predictor| has_levels(&row.config, predictor) as u8 as f64)
+                .collect()
+        })
+        .collect::<Vec<Vec<f64>>>();
+    let y = rows
+        .iter()
+        .filter_map(|row| response.value(row))
+        .collect::<Vec<_>>();
+
+    Ok(Model {
+        formula: format!("{} ~ {}", response.name(), formula_terms.join(" + ")),
+        fit: regression::ols(&terms, &x, &y)?,
+        missing,
+        constant,
+    })
+}
+
+fn has_levels(config: &Config, levels: &[(&str, String)]) -> bool {
+    levels
+        .iter()
+        .all(|(factor, level)| factor_level(config, factor) == *level)
+}
+
+/// Tests each interaction by comparing the full model against the model without it.
+fn print_interaction_tests(
+    rows: &[&Row],
+    response: &Objective,
+    args: &AnalyzeArgs,
+    full: &Model,
+) -> Result<()> {
+    let mut table = vec![];
+
+    for (i, interaction) in args.interactions.iter().enumerate() {
+        if full.constant.contains(&interaction.a) || full.constant.contains(&interaction.b) {
+            continue;
+        }
+
+        let mut others = args.interactions.clone();
+        others.remove(i);
+
+        let reduced = fit(rows, response, &args.baselines, &others)?;
+        let test = regression::f_test(&reduced.fit, &full.fit);
+
+        table.push(vec![
+            interaction.name(),
+            test.df.to_string(),
+            format!("{:.3}", test.f),
+            format_p_value(test.p_value),
+            significance_code(test.p_value).to_string(),
+        ]);
+    }
+
+    println!("F tests for each interaction, against the model without it:");
+    println!();
+    println!(
+        "{}",
+        markdown_table(&["interaction", "df", "F", "p_value", ""], &table)
+    );
+
+    Ok(())
+}
+
+/// Plots the mean of the response for each value of the first axis, with a line for each
+/// value of the second. Lines that aren't parallel suggest an interaction.
+fn interaction_plot(rows: &[&Row], response: &Objective, interaction: &Interaction) -> String {
+    let levels = |factor| {
+        rows.iter()
+            .map(|row| factor_level(&row.config, factor))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    let a_levels = levels(interaction.a);
+    let b_levels = levels(interaction.b);
+
+    let series = b_levels
+        .iter()
+        .map(|b_level| Series {
+            name: b_level.clone(),
+            values: a_levels
+                .iter()
+                .map(|a_level| {
+                    let values = rows
+                        .iter()
+                        .filter(|row| {
+                            factor_level(&row.config, interaction.a) == *a_level
+                                && factor_level(&row.config, interaction.b) == *b_level
+                        })
+                        .filter_map(|row| response.value(row))
+                        .collect::<Vec<_>>();
+
+                    (!values.is_empty()).then(|| stats::mean(&values))
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    plot::line_chart(
+        &format!(
+            "Mean {} by {} and {}",
+            response.name(),
+            interaction.a,
+            interaction.b
+        ),
+        interaction.a,
+        &a_levels,
+        interaction.b,
+        &series,
+        |value| response.format(value),
+    )
+}
+
+/// The baseline of a factor and every level of it in the data, sorted by name. Without a
+/// `--baseline` for the factor, falls back to the first level in the data when the default
+/// baseline isn't there.
+fn factor_levels(
+    rows: &[&Row],
+    factor: &str,
+    baselines: &[Baseline],
+) -> Result<(String, BTreeSet<String>)> {
+    let levels = rows
+        .iter()
+        .map(|row| factor_level(&row.config, factor))
+        .collect::<BTreeSet<_>>();
+
+    let find = |requested: &str| {
+        levels
+            .iter()
+            .find(|level| level.eq_ignore_ascii_case(requested))
+            .cloned()
+    };
+
+    let Some(requested) = baselines
+        .iter()
+        .rev()
+        .find(|baseline| baseline.factor == factor)
+        .map(|baseline| baseline.level.as_str())
+    else {
+        let default = DEFAULT_BASELINES
+            .iter()
+            .find(|(name, _)| *name == factor)
+            .and_then(|(_, level)| find(level));
+        let baseline = default.unwrap_or_else(|| levels.first().cloned().unwrap_or_default());
+
+        return Ok((baseline, levels));
+    };
+
+    let Some(baseline) = find(requested) else {
+        anyhow::bail!(
+            "The baseline {factor}={requested} isn't in the data. Pass --baseline {factor}=<value> with one of {}",
+            levels.iter().join(", ")
+        );
+    };
+
+    Ok((baseline, levels))
+}
+
+/// The value of a factor in a configuration, as written in results files.
+pub fn factor_level(config: &Config, factor: &str) -> String {
+    match factor {
+        "opt_level" => format!("{:?}", config.cargo.opt_level),
+        "wasm_opt" => format!("{:?}", config.wasm_opt),
+        "lto" => format!("{:?}", config.cargo.lto),
+        "codegen_units" => format!("{:?}", config.cargo.codegen_units),
+        "strip" => format!("{:?}", config.cargo.strip),
+        "panic" => format!("{:?}", config.cargo.panic),
+        _ => unreachable!("Unknown factor {factor}"),
+    }
+}
+
+fn print_fit(model: &Model) {
+    let fit = &model.fit;
+
+    println!("Formula: {}", model.formula);
+    println!();
+
+    let table = fit
+        .coefficients
+        .iter()
+        .map(|coefficient| {
+            vec![
+                coefficient.term.clone(),
+                format_number(coefficient.estimate),
+                format_number(coefficient.std_error),
+                format!("{:.3}", coefficient.t),
+                format_p_value(coefficient.p_value),
+                significance_code(coefficient.p_value).to_string(),
+                format!(
+                    "{}..{}",
+                    format_number(coefficient.ci_low),
+                    format_number(coefficient.ci_high)
+                ),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    println!(
+        "{}",
+        markdown_table(
+            &[
+                "term",
+                "estimate",
+                "std_error",
+                "t",
+                "p_value",
+                "",
+                "95% CI"
+            ],
+            &table
+        )
+    );
+    println!(
+        "Residual standard error: {} on {} degrees of freedom, {} observations.",
+        format_number(fit.sigma()),
+        fit.df,
+        fit.observations
+    );
+    println!(
+        "R²: {:.4}, adjusted R²: {:.4}",
+        fit.r_squared, fit.adj_r_squared
+    );
+
+    for term in &model.missing {
+        println!("{term} isn't estimated, because no configuration has that combination.");
+    }
+    for factor in &model.constant {
+        println!("{factor} isn't in the model, because every configuration has the same value.");
+    }
+}
+
+/// R-style significance code for a p-value.
+pub fn significance_code(p: f64) -> &'static str {
+    if p < 0.001 {
+        "***"
+    } else if p < 0.01 {
+        "**"
+    } else if p < 0.05 {
+        "*"
+    } else if p < 0.1 {
+        "."
+    } else {
+        " "
+    }
+}
+
+pub fn format_p_value(p: f64) -> String {
+    if p < 2e-16 {
+        "<2e-16".to_string()
+    } else if p < 0.001 {
+        format!("{p:.2e}")
+    } else {
+        format!("{p:.4}")
+    }
+}
+
+/// Formats a coefficient with four significant digits, without switching to exponents for
+/// large values like sizes in bytes.
+fn format_number(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{value}");
+    }
+
+    let digits = (3 - value.abs().log10().floor() as i32).max(0) as usize;
+    format!("{value:.digits$}")
+}
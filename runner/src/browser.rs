@@ -0,0 +1,393 @@
+//! A minimal Chrome DevTools Protocol client for driving a local Chromium.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tungstenite::{Message, WebSocket};
+
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(20);
+const CALL_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[cfg(target_os = "windows")]
+const BROWSER_CANDIDATES: &[&str] = &[
+    "chrome.exe",
+    "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe",
+    "C:\\Program Files (x86)\\Google\\Chrome\\Application\\chrome.exe",
+    "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
+];
+#[cfg(target_os = "macos")]
+const BROWSER_CANDIDATES: &[&str] = &[
+    "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+    "/Applications/Chromium.app/Contents/MacOS/Chromium",
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const BROWSER_CANDIDATES: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+];
+
+/// Something that happened in a page.
+#[derive(Debug)]
+pub enum PageEvent {
+    /// A `console.*` call, with its arguments joined by spaces.
+    Console(String),
+    /// An uncaught exception.
+    Exception(String),
+}
+
+/// Looks for a Chromium-based browser in the usual install locations and in `PATH`.
+pub fn find_browser() -> Option<PathBuf> {
+    for candidate in BROWSER_CANDIDATES {
+        let path = Path::new(candidate);
+        if path.is_absolute() {
+            if path.exists() {
+                return Some(path.to_path_buf());
+            }
+            continue;
+        }
+
+        let paths = std::env::var_os("PATH")?;
+        for dir in std::env::split_paths(&paths) {
+            let path = dir.join(candidate);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+/// A running browser, controlled over the DevTools protocol.
+///
+/// The browser process is killed and its temporary profile removed when this is dropped.
+pub struct Browser {
+    child: Child,
+    user_data_dir: PathBuf,
+    socket: WebSocket<TcpStream>,
+    next_id: u64,
+    events: VecDeque<Value>,
+}
+
+impl Browser {
+    /// Launches the browser at `executable` with a fresh profile and connects to it.
+    pub fn launch(executable: &Path) -> Result<Self> {
+        let user_data_dir =
+            std::env::temp_dir().join(format!("bevy_wasm_bench-profile-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&user_data_dir);
+
+        let mut child = Command::new(executable)
+            .arg("--remote-debugging-port=0")
+            .arg(format!("--user-data-dir={}", user_data_dir.display()))
+            .args([
+                "--no-first-run",
+                "--no-default-browser-check",
+                "--disable-background-timer-throttling",
+                "--disable-backgrounding-occluded-windows",
+                "--disable-renderer-backgrounding",
+                "--window-size=1920,1080",
+                "about:blank",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Launching {}", executable.display()))?;
+
+        // The browser announces its DevTools endpoint on stderr. Keep draining stderr afterwards
+        // so that the browser never blocks on a full pipe.
+
+        let stderr = child.stderr.take().context("Capturing browser stderr")?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if let Some(url) = line.strip_prefix("DevTools listening on ") {
+                    let _ = sender.send(url.trim().to_string());
+                }
+            }
+        });
+
+        let url = match receiver.recv_timeout(LAUNCH_TIMEOUT) {
+            Ok(url) => url,
+            Err(_) => {
+                let _ = child.kill();
+                anyhow::bail!("Browser did not report a DevTools endpoint");
+            }
+        };
+
+        let socket = connect(&url).context("Connecting to browser")?;
+
+        Ok(Self {
+            child,
+            user_data_dir,
+            socket,
+            next_id: 0,
+            events: VecDeque::new(),
+        })
+    }
+
+    /// Opens a new tab with a `width`x`height` viewport, navigates it to `url` and returns a
+    /// session id for the page.
+    pub fn open_page(&mut self, url: &str, width: u32, height: u32) -> Result<String> {
+        let target = self.call(None, "Target.createTarget", json!({ "url": "about:blank" }))?;
+        let target_id = target["targetId"]
+            .as_str()
+            .context("Target.createTarget returned no targetId")?;
+
+        let attached = self.call(
+            None,
+            "Target.attachToTarget",
+            json!({ "targetId": target_id, "flatten": true }),
+        )?;
+        let session = attached["sessionId"]
+            .as_str()
+            .context("Target.attachToTarget returned no sessionId")?
+            .to_string();
+
+        self.call(Some(&session), "Runtime.enable", json!({}))?;
+        self.call(Some(&session), "Page.enable", json!({}))?;
+        self.call(
+            Some(&session),
+            "Emulation.setDeviceMetricsOverride",
+            json!({
+                "width": width,
+                "height": height,
+                "deviceScaleFactor": 1,
+                "mobile": false,
+            }),
+        )?;
+
+        let navigated = self.call(Some(&session), "Page.navigate", json!({ "url": url }))?;
+        if let Some(error) = navigated["errorText"].as_str() {
+            anyhow::bail!("Failed to load {url}: {error}");
+        }
+
+        Ok(session)
+    }
+
+    /// Waits for the next console message or exception from the page with the given session,
+    /// returning `None` if `deadline` passes first.
+    pub fn next_page_event(
+        &mut self,
+        session: &str,
+        deadline: Instant,
+    ) -> Result<Option<PageEvent>> {
+        loop {
+            let Some(event) = self.next_event(deadline)? else {
+                return Ok(None);
+            };
+
+            if event["sessionId"].as_str() != Some(session) {
+                continue;
+            }
+
+            let params = &event["params"];
+
+            match event["method"].as_str() {
+                Some("Runtime.consoleAPICalled") => {
+                    let text = params["args"]
+                        .as_array()
+                        .map(|args| args.iter().map(remote_object_text).collect::<Vec<_>>())
+                        .unwrap_or_default()
+                        .join(" ");
+                    return Ok(Some(PageEvent::Console(text)));
+                }
+                Some("Runtime.exceptionThrown") => {
+                    let details = &params["exceptionDetails"];
+                    let text = details["exception"]["description"]
+                        .as_str()
+                        .or_else(|| details["text"].as_str())
+                        .unwrap_or("Unknown exception")
+                        .to_string();
+                    return Ok(Some(PageEvent::Exception(text)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Evaluates a JavaScript expression in the page with the given session and returns its
+    /// value.
+    pub fn evaluate(&mut self, session: &str, expression: &str) -> Result<Value> {
+        let evaluated = self.call(
+            Some(session),
+            "Runtime.evaluate",
+            json!({ "expression": expression, "returnByValue": true }),
+        )?;
+
+        if let Some(exception) = evaluated.get("exceptionDetails") {
+            anyhow::bail!("Evaluating {expression} failed: {}", exception["text"]);
+        }
+
+        Ok(evaluated["result"]["value"].clone())
+    }
+
+    /// The browser's product and version, like `Chrome/126.0.6478.127`.
+    pub fn version(&mut self) -> Result<String> {
+        let version = self.call(None, "Browser.getVersion", json!({}))?;
+
+        version["product"]
+            .as_str()
+            .map(str::to_string)
+            .context("Browser.getVersion returned no product")
+    }
+
+    /// A description of the GPU the browser renders with, if it reports one.
+    pub fn gpu(&mut self) -> Result<Option<String>> {
+        let info = self.call(None, "SystemInfo.getInfo", json!({}))?;
+        let gpu = &info["gpu"];
+
+        let devices = gpu["devices"].as_array().cloned().unwrap_or_default();
+        let device = devices
+            .iter()
+            .filter(|device| device["active"].as_bool() != Some(false))
+            .filter_map(|device| device["deviceString"].as_str())
+            .find(|name| !name.is_empty());
+
+        // Some platforms only report PCI ids for devices, but the GL renderer string names the
+        // GPU too.
+        let renderer = gpu["auxAttributes"]["glRenderer"].as_str();
+
+        Ok(device.or(renderer).map(str::to_string))
+    }
+
+    /// Asks the browser to close and waits for it to exit.
+    pub fn close(mut self) -> Result<()> {
+        let id = self.send(None, "Browser.close", json!({}))?;
+        // The browser may exit before acknowledging the command.
+        let _ = self.wait_for_response(id, Instant::now() + CALL_TIMEOUT);
+
+        Ok(())
+    }
+
+    /// Sends a command and waits for its result.
+    fn call(&mut self, session: Option<&str>, method: &str, params: Value) -> Result<Value> {
+        let id = self.send(session, method, params)?;
+        self.wait_for_response(id, Instant::now() + CALL_TIMEOUT)
+            .with_context(|| format!("Calling {method}"))
+    }
+
+    fn send(&mut self, session: Option<&str>, method: &str, params: Value) -> Result<u64> {
+        self.next_id += 1;
+        let id = self.next_id;
+
+        let mut message = json!({ "id": id, "method": method, "params": params });
+        if let Some(session) = session {
+            message["sessionId"] = json!(session);
+        }
+
+        self.socket
+            .send(Message::text(message.to_string()))
+            .context("Sending DevTools command")?;
+
+        Ok(id)
+    }
+
+    fn wait_for_response(&mut self, id: u64, deadline: Instant) -> Result<Value> {
+        loop {
+            let Some(message) = self.read_message(deadline)? else {
+                anyhow::bail!("Timed out waiting for a response");
+            };
+
+            if message["id"].as_u64() == Some(id) {
+                if let Some(error) = message.get("error") {
+                    anyhow::bail!("DevTools error: {}", error);
+                }
+                return Ok(message["result"].clone());
+            }
+
+            if message.get("method").is_some() {
+                self.events.push_back(message);
+            }
+        }
+    }
+
+    fn next_event(&mut self, deadline: Instant) -> Result<Option<Value>> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(Some(event));
+        }
+
+        loop {
+            let Some(message) = self.read_message(deadline)? else {
+                return Ok(None);
+            };
+
+            if message.get("method").is_some() {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    /// Reads the next JSON message from the socket, returning `None` if `deadline` passes first.
+    fn read_message(&mut self, deadline: Instant) -> Result<Option<Value>> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+
+            self.socket
+                .get_ref()
+                .set_read_timeout(Some(remaining.min(Duration::from_millis(250))))?;
+
+            match self.socket.read() {
+                Ok(Message::Text(text)) => {
+                    return serde_json::from_str(text.as_str())
+                        .map(Some)
+                        .context("Parsing DevTools message");
+                }
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(e).context("Reading DevTools message"),
+            }
+        }
+    }
+}
+
+impl Drop for Browser {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.user_data_dir);
+    }
+}
+
+fn connect(url: &str) -> Result<WebSocket<TcpStream>> {
+    let address = url
+        .strip_prefix("ws://")
+        .and_then(|rest| rest.split('/').next())
+        .with_context(|| format!("Unexpected DevTools endpoint: {url}"))?;
+
+    let stream = TcpStream::connect(address)?;
+    let (socket, _) = tungstenite::client(url, stream)
+        .map_err(|e| anyhow::anyhow!("WebSocket handshake failed: {e}"))?;
+
+    Ok(socket)
+}
+
+/// Formats a `Runtime.RemoteObject` roughly the way the console would.
+fn remote_object_text(object: &Value) -> String {
+    match &object["value"] {
+        Value::String(s) => s.clone(),
+        Value::Null => object["description"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,283 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use size::Size;
+
+use crate::compress::Codec;
+use crate::options::*;
+use crate::pipeline::Activity;
+use crate::wasm_size::SizeReport;
+use crate::{NAME, PROFILE, WASM_OPT_COMMAND};
+
+const BUILD_TIME_FILE: &str = "bench-build-time";
+
+/// The output of building a single configuration, ready to be measured in the browser.
+#[derive(Debug)]
+pub struct Artifact {
+    pub config: Config,
+    /// Directory containing the wasm-bindgen output.
+    pub dir: PathBuf,
+    pub build_time: Duration,
+    pub wasm_opt_time: Duration,
+    pub size: u64,
+    /// Compressed size for each codec that was measured.
+    pub compressed_sizes: Vec<(Codec, u64)>,
+    /// Whether the build or wasm-opt pass overlapped with other work, which skews its timing.
+    pub contended: bool,
+}
+
+impl Artifact {
+    pub fn compressed_size(&self, codec: Codec) -> Option<u64> {
+        self.compressed_sizes
+            .iter()
+            .find(|(c, _)| *c == codec)
+            .map(|(_, size)| *size)
+    }
+}
+
+pub struct BuildOptions {
+    /// Reuse an earlier clean build of the same options, along with its recorded build time.
+    pub cache: bool,
+    /// Codecs to measure compressed sizes with.
+    pub codecs: Vec<Codec>,
+    /// Where to write the size breakdown of each build.
+    pub sizes_dir: PathBuf,
+}
+
+/// Builds the bench with the given cargo options, then runs wasm-bindgen and wasm-opt for
+/// each of `wasm_opts`.
+pub fn build(
+    cargo: CargoOptions,
+    wasm_opts: &[WasmOpt],
+    options: &BuildOptions,
+    activity: &Activity,
+) -> Result<Vec<Artifact>> {
+    let name = cargo.name();
+    let target_dir = cargo.target_dir();
+    let wasm_path = target_dir.join(format!("wasm32-unknown-unknown/{}/bench.wasm", PROFILE));
+
+    let cached_build = if options.cache {
+        read_build_time(&target_dir)
+    } else {
+        None
+    };
+
+    // Clean
+
+    if cached_build.is_none() {
+        println!("[{name}] Cleaning up.");
+
+        Command::new("cargo")
+            .arg("clean")
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .output()
+            .context("Running cargo clean")?;
+    }
+
+    // Create cargo options
+
+    std::fs::create_dir_all(&target_dir)?;
+    let config_path = target_dir.join("config.toml");
+    std::fs::write(&config_path, cargo.config_toml())?;
+
+    // Build wasm
+
+    println!(
+        "[{name}] Building with OptLevel::{:?}, Lto::{:?}, CodegenUnits::{:?}, Strip::{:?} Panic::{:?}",
+        cargo.opt_level, cargo.lto, cargo.codegen_units, cargo.strip, cargo.panic
+    );
+
+    let task = activity.start();
+    let now = Instant::now();
+
+    let output = Command::new("cargo")
+        .arg("build")
+        .arg("-p")
+        .arg("bench")
+        .arg("--target=wasm32-unknown-unknown")
+        .args(["--profile", PROFILE])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .arg("--config")
+        .arg(&config_path)
+        .output()
+        .context("Building bench")?;
+    if !output.status.success() {
+        std::io::stdout().write_all(&output.stdout)?;
+        std::io::stderr().write_all(&output.stderr)?;
+        anyhow::bail!("Failed to build bench");
+    }
+
+    let elapsed = now.elapsed();
+    let contended = task.finish();
+
+    let (build_time, build_contended) = match cached_build {
+        Some(cached) => {
+            println!("[{name}] Reusing cached build.");
+            cached
+        }
+        None => {
+            write_build_time(&target_dir, elapsed, contended)?;
+            (elapsed, contended)
+        }
+    };
+
+    // wasm-bindgen drops DWARF `.debug_*` sections from its output, so break down cargo's
+    // output too to see what stripping debug info saves.
+
+    let report =
+        SizeReport::new(&std::fs::read(&wasm_path)?).context("Analyzing size of cargo output")?;
+    report.write(&options.sizes_dir.join(format!("{name}-cargo.json")))?;
+
+    println!(
+        "[{name}] Cargo output: {}, code {}, data {}, names {}, debug info {}",
+        Size::from_bytes(report.total),
+        Size::from_bytes(report.code),
+        Size::from_bytes(report.data),
+        Size::from_bytes(report.names),
+        Size::from_bytes(report.debug_info)
+    );
+
+    let mut artifacts = vec![];
+
+    for wasm_opt in wasm_opts.iter().copied() {
+        let out_dir = target_dir.join(format!("web-{:?}", wasm_opt));
+        // Don't leave compressed files from an earlier run lying around to be served.
+        let _ = std::fs::remove_dir_all(&out_dir);
+
+        // Bindgen
+
+        println!("[{name}] Running bindgen for WasmOpt::{:?}.", wasm_opt);
+
+        let output = Command::new("wasm-bindgen")
+            .args(["--out-name", NAME, "--out-dir"])
+            .arg(&out_dir)
+            .args(["--target", "web"])
+            .arg(&wasm_path)
+            .output()
+            .context("Running wasm-bindgen")?;
+        if !output.status.success() {
+            std::io::stdout().write_all(&output.stdout)?;
+            std::io::stderr().write_all(&output.stderr)?;
+            anyhow::bail!("Failed to run wasm-bindgen");
+        }
+
+        println!("[{name}] Running wasm-opt with WasmOpt::{:?}", wasm_opt);
+
+        let bindgen_wasm_path = out_dir.join(format!("{}_bg.wasm", NAME));
+
+        let task = activity.start();
+        let now = Instant::now();
+
+        if wasm_opt.enabled() {
+            let output = Command::new(WASM_OPT_COMMAND)
+                .args(wasm_opt.args())
+                .arg(&bindgen_wasm_path)
+                .arg("-o")
+                .arg(&bindgen_wasm_path)
+                .output()
+                .context("Running wasm-opt")?;
+            if !output.status.success() {
+                std::io::stdout().write_all(&output.stdout)?;
+                std::io::stderr().write_all(&output.stderr)?;
+                anyhow::bail!("Failed to run wasm-opt");
+            }
+        }
+
+        let wasm_opt_time = if wasm_opt.enabled() {
+            now.elapsed()
+        } else {
+            Duration::default()
+        };
+        let wasm_opt_contended = task.finish() && wasm_opt.enabled();
+
+        let wasm = std::fs::read(&bindgen_wasm_path)?;
+
+        // Break down where the bytes go
+
+        let report = SizeReport::new(&wasm).context("Analyzing wasm size")?;
+        report.write(
+            &options
+                .sizes_dir
+                .join(format!("{name}-{:?}.json", wasm_opt)),
+        )?;
+
+        println!(
+            "[{name}] WasmOpt::{:?}: code {}, data {}, names {}, debug info {}",
+            wasm_opt,
+            Size::from_bytes(report.code),
+            Size::from_bytes(report.data),
+            Size::from_bytes(report.names),
+            Size::from_bytes(report.debug_info)
+        );
+
+        // Compress to measure transfer sizes. Some codecs' output is kept so that the web
+        // server can serve it.
+
+        let mut compressed_sizes = vec![];
+        for codec in &options.codecs {
+            let compressed = codec
+                .compress(&wasm)
+                .with_context(|| format!("Compressing wasm with {codec:?}"))?;
+
+            if let Some(extension) = codec.extension() {
+                std::fs::write(
+                    out_dir.join(format!("{}_bg.wasm.{}", NAME, extension)),
+                    &compressed,
+                )?;
+            }
+
+            compressed_sizes.push((*codec, compressed.len() as u64));
+        }
+
+        println!(
+            "[{name}] WasmOpt::{:?}: {} ({}), {:.2?} (+{:.2?} wasm-opt)",
+            wasm_opt,
+            Size::from_bytes(wasm.len()),
+            compressed_sizes
+                .iter()
+                .map(|(codec, size)| format!("{} {:?}", Size::from_bytes(*size), codec))
+                .collect::<Vec<_>>()
+                .join(", "),
+            build_time,
+            wasm_opt_time
+        );
+
+        artifacts.push(Artifact {
+            config: Config { cargo, wasm_opt },
+            dir: out_dir,
+            build_time,
+            wasm_opt_time,
+            size: wasm.len() as u64,
+            compressed_sizes,
+            contended: build_contended || wasm_opt_contended,
+        });
+    }
+
+    Ok(artifacts)
+}
+
+/// Reads the clean build time recorded for a cached target directory, and whether that
+/// build was contended.
+fn read_build_time(target_dir: &Path) -> Option<(Duration, bool)> {
+    let contents = std::fs::read_to_string(target_dir.join(BUILD_TIME_FILE)).ok()?;
+    let mut lines = contents.lines();
+
+    let secs = lines.next()?.trim().parse::<f32>().ok()?;
+    let contended = lines.next()?.trim().parse::<bool>().ok()?;
+
+    Some((Duration::from_secs_f32(secs), contended))
+}
+
+/// Records the clean build time for a target directory so that cached runs can report it.
+fn write_build_time(target_dir: &Path, build_time: Duration, contended: bool) -> Result<()> {
+    std::fs::write(
+        target_dir.join(BUILD_TIME_FILE),
+        format!("{}\n{}\n", build_time.as_secs_f32(), contended),
+    )
+    .context("Writing build time")
+}
@@ -0,0 +1,377 @@
+//! Command line arguments.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use itertools::Itertools;
+use strum::IntoEnumIterator;
+
+use crate::analyze::{Baseline, Interaction};
+use crate::build::BuildOptions;
+use crate::compress::{Codec, DEFAULT_CODECS};
+use crate::database::{DATABASE_PATH, RUN_FIELDS};
+use crate::design::{Design, DesignKind};
+use crate::matrix::{Axes, Matrix, MATRIX_PATH};
+use crate::options::Config;
+use crate::report::{Objective, Weights};
+use crate::results;
+use crate::search::SEARCH_CSV_PATH;
+use crate::{CSV_PATH, OUT_DIR};
+
+/// Builds the bevy wasm bench with many combinations of size and speed optimizations and
+/// measures each one in a browser.
+#[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Arguments for `run`, which is what happens without a subcommand.
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Build and measure every configuration in the matrix. This is the default.
+    Run(RunArgs),
+    /// Build every configuration in the matrix without measuring them.
+    BuildOnly(BuildOnlyArgs),
+    /// Measure the bench that's already built into a web directory.
+    Measure(MeasureDirArgs),
+    /// Measure configurations adaptively, spending more repetitions on those near the Pareto
+    /// front of frame time, gzipped size and build time.
+    Search(SearchArgs),
+    /// Summarize a results file.
+    Report(ReportArgs),
+    /// Compare two results files configuration by configuration.
+    Compare(CompareArgs),
+    /// Estimate the effect of each option on a results column with a linear model.
+    Analyze(AnalyzeArgs),
+    /// Rewrite results files from older versions of the runner in the current schema.
+    Migrate(MigrateArgs),
+    /// Import results files into a results database.
+    Import(ImportArgs),
+    /// Print a results column for each configuration across runs in a results database, like
+    /// each bevy version.
+    Query(QueryArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub matrix: MatrixArgs,
+    #[command(flatten)]
+    pub build: BuildArgs,
+    #[command(flatten)]
+    pub measure: MeasureArgs,
+    /// Where to write results. Samples and metadata are written next to it.
+    #[arg(long, short, default_value = CSV_PATH)]
+    pub output: PathBuf,
+    /// Write results to `results/<commit>_<os>_<cpu>_<gpu>.csv` instead of `--output`.
+    #[arg(long)]
+    pub auto_name: bool,
+    /// Continue an interrupted run, skipping configurations already in the output.
+    #[arg(long)]
+    pub resume: bool,
+    /// Also write results to this SQLite database, which is created if it doesn't exist.
+    #[arg(long)]
+    pub database: Option<PathBuf>,
+    /// Print what would be built and measured, and how long it might take, then exit.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    #[command(flatten)]
+    pub matrix: MatrixArgs,
+    #[command(flatten)]
+    pub build: BuildArgs,
+    #[command(flatten)]
+    pub measure: MeasureArgs,
+    /// Where to write results for every configuration measured. Samples and metadata are
+    /// written next to it.
+    #[arg(long, short, default_value = SEARCH_CSV_PATH)]
+    pub output: PathBuf,
+    /// Most browser sessions to measure a configuration in. Every configuration starts with
+    /// `--repetitions`, which doubles each round it stays near the front.
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_repetitions: u32,
+    /// Also write results to this SQLite database, which is created if it doesn't exist.
+    #[arg(long)]
+    pub database: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct BuildOnlyArgs {
+    #[command(flatten)]
+    pub matrix: MatrixArgs,
+    #[command(flatten)]
+    pub build: BuildArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct MeasureDirArgs {
+    /// The directory holding the built bench and its page.
+    #[arg(long, default_value = OUT_DIR)]
+    pub dir: PathBuf,
+    #[command(flatten)]
+    pub measure: MeasureArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// The results file to summarize.
+    #[arg(default_value = CSV_PATH)]
+    pub input: PathBuf,
+    /// Columns to find the Pareto front over, all minimized. Columns joined by `+` are added
+    /// together.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_objective,
+        default_values = ["frame_time", "size_gzipped", "build_time+wasm_opt_time"]
+    )]
+    pub objectives: Vec<Objective>,
+    /// Relative importance of each objective, like `2,1,0`, to rank the front by. Can be
+    /// repeated. By default the front is ranked with equal weights and by each objective alone.
+    #[arg(long, value_name = "WEIGHTS", value_parser = parse_weights)]
+    pub weights: Vec<Weights>,
+    /// Number of configurations to recommend for each weighting.
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+    /// Also write the front and recommendations to this JSON file.
+    #[arg(long)]
+    pub json: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    /// The results file to analyze.
+    #[arg(default_value = CSV_PATH)]
+    pub input: PathBuf,
+    /// Columns to fit a model for, each on its own. Columns joined by `+` are added together.
+    #[arg(
+        long = "response",
+        value_name = "RESPONSE",
+        value_delimiter = ',',
+        value_parser = parse_objective,
+        default_values = [
+            "frame_time",
+            "size_gzipped",
+            "build_time",
+            "wasm_opt_time",
+            "build_time+wasm_opt_time"
+        ]
+    )]
+    pub responses: Vec<Objective>,
+    /// The value of an axis to measure the effects of its other values against, like
+    /// `opt_level=S`. Can be repeated. Defaults to `Three`, `None`, `Off`, `Default`, `None`
+    /// and `Unwind`.
+    #[arg(long = "baseline", value_name = "AXIS=VALUE", value_parser = parse_baseline)]
+    pub baselines: Vec<Baseline>,
+    /// Also fit the interaction between two axes, like `opt_level:wasm_opt`. Can be repeated.
+    #[arg(long = "interaction", value_name = "AXIS:AXIS", value_parser = parse_interaction)]
+    pub interactions: Vec<Interaction>,
+    /// Where to write an interaction plot for each response and interaction.
+    #[arg(long, default_value = "plots")]
+    pub plots: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    /// The results file to compare against.
+    pub baseline: PathBuf,
+    /// The results file to compare.
+    pub candidate: PathBuf,
+    /// Columns to compare, all better when lower. Columns joined by `+` are added together.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_objective,
+        default_values = ["frame_time", "size_gzipped", "build_time+wasm_opt_time"]
+    )]
+    pub metrics: Vec<Objective>,
+    /// Smallest change, in percent, that counts as a regression or improvement.
+    #[arg(long, default_value_t = 5.0)]
+    pub threshold: f64,
+    /// Significance level for changes that can be tested, because both files have several
+    /// samples of them.
+    #[arg(long, default_value_t = 0.05)]
+    pub alpha: f64,
+    /// Only list configurations with a regression or improvement.
+    #[arg(long)]
+    pub changed_only: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    /// The results files to rewrite in place.
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// The results files to import. Their samples and metadata files are imported too.
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+    #[arg(long, default_value = DATABASE_PATH)]
+    pub database: PathBuf,
+    /// The bevy version, for files whose metadata doesn't record it.
+    #[arg(long)]
+    pub bevy: Option<String>,
+    /// The rustc version, for files whose metadata doesn't record it.
+    #[arg(long)]
+    pub rustc: Option<String>,
+    /// The wasm-opt version, for files whose metadata doesn't record it.
+    #[arg(long)]
+    pub wasm_opt_version: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// The results column to print. Columns joined by `+` are added together.
+    #[arg(value_parser = parse_objective)]
+    pub column: Objective,
+    /// Only include these values of an axis, like `opt_level=S,Z`. Can be repeated.
+    #[arg(long, value_name = "AXIS=VALUES", value_parser = parse_filter)]
+    pub only: Vec<Axes>,
+    /// The run field to compare across, like `bevy`, `rustc` or `cpu`. `wasm_opt` is the
+    /// wasm-opt version.
+    #[arg(long, default_value = "bevy", value_parser = RUN_FIELDS)]
+    pub by: String,
+    #[arg(long, default_value = DATABASE_PATH)]
+    pub database: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct MatrixArgs {
+    /// The matrix of configurations to test.
+    #[arg(long, default_value = MATRIX_PATH)]
+    pub matrix: PathBuf,
+    /// Only test these values of an axis, like `opt_level=S,Z`. Can be repeated.
+    #[arg(long, value_name = "AXIS=VALUES", value_parser = parse_filter)]
+    pub only: Vec<Axes>,
+    /// Skip configurations with these values of an axis, like `wasm_opt=Both`. Can be
+    /// repeated.
+    #[arg(long, value_name = "AXIS=VALUES", value_parser = parse_filter)]
+    pub exclude: Vec<Axes>,
+    /// Which configurations of the filtered matrix to run.
+    #[arg(long, value_enum, default_value_t = DesignKind::Full)]
+    pub design: DesignKind,
+    /// Number of configurations for the `latin-hypercube` and `random` designs.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples: Option<u32>,
+    /// Seed for the `latin-hypercube` and `random` designs, so they can be repeated.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+impl MatrixArgs {
+    /// Loads the matrix, applies the filters and design, and expands it into configurations.
+    pub fn configs(&self) -> Result<(Vec<Config>, Design)> {
+        let mut matrix = Matrix::load_or_default(&self.matrix)?;
+        matrix.filter(&self.only, &self.exclude)?;
+
+        matrix
+            .expand_design(self.design, self.samples.map(|n| n as usize), self.seed)
+            .context("Loading benchmark matrix")
+    }
+}
+
+fn parse_filter(filter: &str) -> Result<Axes, String> {
+    Axes::parse_filter(filter).map_err(|e| e.to_string())
+}
+
+fn parse_objective(objective: &str) -> Result<Objective, String> {
+    Objective::parse(objective).map_err(|e| e.to_string())
+}
+
+fn parse_baseline(baseline: &str) -> Result<Baseline, String> {
+    Baseline::parse(baseline).map_err(|e| e.to_string())
+}
+
+fn parse_interaction(interaction: &str) -> Result<Interaction, String> {
+    Interaction::parse(interaction).map_err(|e| e.to_string())
+}
+
+fn parse_weights(weights: &str) -> Result<Weights, String> {
+    Weights::parse(weights).map_err(|e| e.to_string())
+}
+
+#[derive(Args, Debug)]
+pub struct BuildArgs {
+    /// Reuse earlier builds and their recorded build times instead of building from scratch.
+    #[arg(long)]
+    pub cache: bool,
+    /// Build up to this many upcoming configurations in the background while measuring.
+    #[arg(long, default_value_t = 0)]
+    pub jobs: usize,
+    /// Codecs to measure compressed sizes with. Gzip is always included.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_codec,
+        default_values_t = DEFAULT_CODECS.to_vec()
+    )]
+    pub codecs: Vec<Codec>,
+    /// Where to write the wasm size breakdown of each build. Defaults to `<output>-sizes` next
+    /// to the results file, like `out-sizes` for `out.csv`.
+    #[arg(long)]
+    pub sizes: Option<PathBuf>,
+}
+
+impl BuildArgs {
+    /// The build options for a run writing its results to `csv_path`.
+    pub fn options(&self, csv_path: &Path) -> BuildOptions {
+        let mut codecs = vec![Codec::Gzip];
+        for codec in &self.codecs {
+            if !codecs.contains(codec) {
+                codecs.push(*codec);
+            }
+        }
+
+        BuildOptions {
+            cache: self.cache,
+            codecs,
+            sizes_dir: self
+                .sizes
+                .clone()
+                .unwrap_or_else(|| results::sizes_dir(csv_path)),
+        }
+    }
+}
+
+fn parse_codec(codec: &str) -> Result<Codec, String> {
+    codec
+        .parse()
+        .map_err(|_| format!("expected one of {}", Codec::iter().join(", ")))
+}
+
+#[derive(Args, Debug)]
+pub struct MeasureArgs {
+    /// The Chrome or Chromium executable to measure in. Found automatically by default.
+    #[arg(long)]
+    pub browser: Option<PathBuf>,
+    /// Serve precompressed `.br` and `.gz` files, the way a production server would.
+    #[arg(long)]
+    pub precompressed: bool,
+    /// Number of browser sessions to measure each configuration in.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub repetitions: u32,
+    /// Seconds to wait for the bench to report a result.
+    #[arg(long, default_value_t = 30)]
+    pub timeout: u64,
+    /// Times to retry a failed browser session.
+    #[arg(long, default_value_t = 3)]
+    pub retries: usize,
+}
+
+impl MeasureArgs {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+}
@@ -0,0 +1,262 @@
+//! Compares two results files configuration by configuration.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::analyze::{format_p_value, significance_code};
+use crate::cli::CompareArgs;
+use crate::options::Config;
+use crate::report::{markdown_table, Objective};
+use crate::results::{self, Row};
+use crate::stats;
+
+/// Prints the change in each metric for every configuration in both files, flagging
+/// regressions and improvements.
+///
+/// Files of any schema can be compared. Configurations are matched on every configuration
+/// column, with older files that lack a column read as its default.
+pub fn compare(args: &CompareArgs) -> Result<()> {
+    let baseline_rows = results::read_results(&args.baseline)?;
+    let candidate_rows = results::read_results(&args.candidate)?;
+
+    for path in [&args.baseline, &args.candidate] {
+        if results::schema_version(path)? < 2 {
+            println!(
+                "{} has no panic column, so its configurations are compared as panic = Unwind.",
+                path.display()
+            );
+            println!();
+        }
+    }
+
+    let baseline = baseline_rows
+        .iter()
+        .map(|row| (row.config, row))
+        .collect::<HashMap<Config, &Row>>();
+    let pairs = candidate_rows
+        .iter()
+        .filter_map(|row| Some((*baseline.get(&row.config)?, row)))
+        .collect::<Vec<_>>();
+
+    if pairs.is_empty() {
+        anyhow::bail!(
+            "{} and {} have no configurations in common",
+            args.baseline.display(),
+            args.candidate.display()
+        );
+    }
+
+    // Compare each metric of each configuration.
+
+    let mut table = vec![];
+    let mut changes = vec![vec![]; args.metrics.len()];
+
+    for (base, row) in &pairs {
+        let mut cells = vec![
+            row.config.cargo.name(),
+            format!("{:?}", row.config.wasm_opt),
+        ];
+        let mut flagged = false;
+
+        for (i, metric) in args.metrics.iter().enumerate() {
+            let change = Change::new(metric, base, row, args.threshold, args.alpha);
+            flagged |= change.verdict.is_some();
+
+            cells.push(format_value(metric, change.before));
+            cells.push(format_value(metric, change.after));
+            cells.push(change.describe());
+
+            changes[i].push(change);
+        }
+
+        if flagged || !args.changed_only {
+            table.push(cells);
+        }
+    }
+
+    let mut header = vec!["cargo".to_string(), "wasm_opt".to_string()];
+    for metric in &args.metrics {
+        header.push(format!("{} (baseline)", metric.name()));
+        header.push(metric.name());
+        header.push("change".to_string());
+    }
+
+    if table.is_empty() {
+        println!(
+            "No configuration changed by {}% or more in {}.",
+            args.threshold,
+            args.metrics
+                .iter()
+                .map(Objective::name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    } else {
+        println!(
+            "{}",
+            markdown_table(
+                &header.iter().map(String::as_str).collect::<Vec<_>>(),
+                &table
+            )
+        );
+    }
+
+    // Summarize each metric across configurations.
+
+    for (metric, changes) in args.metrics.iter().zip(&changes) {
+        let count = |verdict| {
+            changes
+                .iter()
+                .filter(|change| change.verdict == Some(verdict))
+                .count()
+        };
+        let relative = changes
+            .iter()
+            .filter_map(|change| change.relative)
+            .collect::<Vec<_>>();
+
+        let mut summary = format!(
+            "{}: {} regressions and {} improvements.",
+            metric.name(),
+            count(Verdict::Regression),
+            count(Verdict::Improvement)
+        );
+
+        if !relative.is_empty() {
+            let mean = stats::mean(&relative);
+            summary.push_str(&format!(
+                " Mean change {:+.1}% over {} configurations",
+                mean * 100.0,
+                relative.len()
+            ));
+
+            // A paired t-test of whether the changes are centered on zero.
+            let stddev = stats::stddev(&relative);
+            if stddev > 0.0 {
+                let t = mean / (stddev / (relative.len() as f64).sqrt());
+                let p = stats::t_p_value(t, (relative.len() - 1) as f64);
+                summary.push_str(&format!(
+                    " (paired t-test p {} {})",
+                    format_p_value(p),
+                    significance_code(p)
+                ));
+            }
+            summary = format!("{}.", summary.trim_end());
+        }
+
+        println!("{summary}");
+    }
+
+    let missing = candidate_rows.len() - pairs.len();
+    if missing > 0 {
+        println!(
+            "{missing} configurations in {} aren't in {}.",
+            args.candidate.display(),
+            args.baseline.display()
+        );
+    }
+    let dropped = baseline_rows.len() - pairs.len();
+    if dropped > 0 {
+        println!(
+            "{dropped} configurations in {} aren't in {}.",
+            args.baseline.display(),
+            args.candidate.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Verdict {
+    Regression,
+    Improvement,
+}
+
+/// The change in one metric of one configuration.
+#[derive(Debug, Clone)]
+struct Change {
+    before: Option<f64>,
+    after: Option<f64>,
+    /// `(after - before) / before`.
+    relative: Option<f64>,
+    /// Welch's t-test of the difference, if both files have several samples of the metric.
+    p_value: Option<f64>,
+    verdict: Option<Verdict>,
+}
+
+impl Change {
+    /// Compares a metric, which is better when lower. A change counts as a regression or
+    /// improvement if it's at least `threshold` percent and, where it can be tested, also
+    /// significant at `alpha`.
+    fn new(metric: &Objective, base: &Row, row: &Row, threshold: f64, alpha: f64) -> Change {
+        let before = metric.value(base);
+        let after = metric.value(row);
+        let relative = match (before, after) {
+            (Some(before), Some(after)) if before != 0.0 => Some((after - before) / before),
+            _ => None,
+        };
+
+        let p_value = spread(metric, base)
+            .zip(spread(metric, row))
+            .map(|(base, row)| stats::welch_p_value(base, row));
+
+        let verdict = relative.filter(|relative| {
+            relative.abs() * 100.0 >= threshold && p_value.is_none_or(|p| p < alpha)
+        });
+
+        Change {
+            before,
+            after,
+            relative,
+            p_value,
+            verdict: verdict.map(|relative| {
+                if relative > 0.0 {
+                    Verdict::Regression
+                } else {
+                    Verdict::Improvement
+                }
+            }),
+        }
+    }
+
+    /// Like `+3.2% (p 0.0012) **regression**`.
+    fn describe(&self) -> String {
+        let mut description = format_change(self.before, self.after);
+
+        if let Some(p) = self.p_value {
+            description.push_str(&format!(" (p {})", format_p_value(p)));
+        }
+        match self.verdict {
+            Some(Verdict::Regression) => description.push_str(" **regression**"),
+            Some(Verdict::Improvement) => description.push_str(" **improvement**"),
+            None => {}
+        }
+
+        description
+    }
+}
+
+/// The mean, standard deviation and number of samples behind a metric, for metrics with a
+/// `_stddev` column like `frame_time`, when there were at least two samples.
+fn spread(metric: &Objective, row: &Row) -> Option<(f64, f64, f64)> {
+    let samples = row.value("samples").filter(|samples| *samples >= 2.0)?;
+    let stddev = row.value(&format!("{}_stddev", metric.name()))?;
+
+    Some((metric.value(row)?, stddev, samples))
+}
+
+fn format_value(metric: &Objective, value: Option<f64>) -> String {
+    value.map(|value| metric.format(value)).unwrap_or_default()
+}
+
+/// The relative change from `before` to `after`, like `+3.2%`.
+pub fn format_change(before: Option<f64>, after: Option<f64>) -> String {
+    match (before, after) {
+        (Some(before), Some(after)) if before != 0.0 => {
+            format!("{:+.1}%", (after - before) / before * 100.0)
+        }
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::Write;
+
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A compression codec and level used to measure transfer size.
+#[derive(EnumIter, EnumString, Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    #[strum(serialize = "gzip")]
+    Gzip,
+    #[strum(serialize = "gzip-best")]
+    GzipBest,
+    #[strum(serialize = "brotli-9")]
+    Brotli9,
+    #[strum(serialize = "brotli-11")]
+    Brotli11,
+    #[strum(serialize = "zstd-19")]
+    Zstd19,
+}
+
+/// Codecs measured unless others are requested. `Gzip` is always measured.
+pub const DEFAULT_CODECS: &[Codec] = &[
+    Codec::Gzip,
+    Codec::GzipBest,
+    Codec::Brotli9,
+    Codec::Brotli11,
+];
+
+impl Codec {
+    /// The file extension used when this codec's output is saved for serving, if it is.
+    ///
+    /// When several brotli levels are measured, the last one wins.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::Gzip => Some("gz"),
+            Self::Brotli9 | Self::Brotli11 => Some("br"),
+            Self::GzipBest | Self::Zstd19 => None,
+        }
+    }
+
+    /// The results column holding sizes compressed with this codec.
+    pub fn column(&self) -> &'static str {
+        match self {
+            Self::Gzip => "size_gzipped",
+            Self::GzipBest => "size_gzip_best",
+            Self::Brotli9 => "size_brotli_9",
+            Self::Brotli11 => "size_brotli_11",
+            Self::Zstd19 => "size_zstd_19",
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Gzip => gzip(data, Compression::default()),
+            Self::GzipBest => gzip(data, Compression::best()),
+            Self::Brotli9 => brotli(data, 9),
+            Self::Brotli11 => brotli(data, 11),
+            Self::Zstd19 => Ok(zstd::encode_all(data, 19)?),
+        }
+    }
+}
+
+fn gzip(data: &[u8], level: Compression) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), level);
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+fn brotli(data: &[u8], quality: i32) -> Result<Vec<u8>> {
+    let params = brotli::enc::BrotliEncoderParams {
+        quality,
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    brotli::BrotliCompress(&mut &data[..], &mut output, &params)?;
+
+    Ok(output)
+}
@@ -0,0 +1,517 @@
+//! An optional SQLite database that accumulates results from many runs along with the
+//! environment each was measured in, so they can be queried across machines, toolchains and
+//! bevy versions.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection};
+
+use crate::cli::{ImportArgs, QueryArgs};
+use crate::metadata::{self, version_key, RunMetadata};
+use crate::options::Config;
+use crate::report::markdown_table;
+use crate::results::{self, CSV_HEADER, SAMPLES_HEADER, SCHEMA_VERSION};
+
+pub const DATABASE_PATH: &str = "results.db";
+
+/// Columns identifying a configuration, which results and samples files start with.
+const CONFIG_COLUMNS: [&str; 6] = [
+    "opt_level",
+    "wasm_opt",
+    "lto",
+    "codegen_units",
+    "strip",
+    "panic",
+];
+
+/// Columns of the `runs` table that results can be grouped by.
+pub const RUN_FIELDS: [&str; 12] = [
+    "source",
+    "rustc",
+    "cargo",
+    "wasm_bindgen",
+    "wasm_opt",
+    "bevy",
+    "git_commit",
+    "os",
+    "os_tag",
+    "cpu",
+    "gpu",
+    "browser",
+];
+
+/// Runs and configurations. The `results` and `samples` tables are created from the headers of
+/// the files they mirror.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    -- The results file the run was written to or imported from.
+    source TEXT NOT NULL,
+    started_unix_secs INTEGER,
+    rustc TEXT,
+    cargo TEXT,
+    wasm_bindgen TEXT,
+    wasm_opt TEXT,
+    bevy TEXT,
+    git_commit TEXT,
+    git_dirty INTEGER,
+    os TEXT,
+    cpu TEXT,
+    cores INTEGER,
+    memory_bytes INTEGER,
+    browser TEXT,
+    gpu TEXT,
+    os_tag TEXT,
+    -- The design as JSON.
+    design TEXT,
+    schema_version INTEGER
+);
+CREATE TABLE IF NOT EXISTS configurations (
+    id INTEGER PRIMARY KEY,
+    opt_level TEXT NOT NULL,
+    wasm_opt TEXT NOT NULL,
+    lto TEXT NOT NULL,
+    codegen_units TEXT NOT NULL,
+    strip TEXT NOT NULL,
+    panic TEXT NOT NULL,
+    UNIQUE (opt_level, wasm_opt, lto, codegen_units, strip, panic)
+);
+";
+
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if needed, and adds any columns that results
+    /// have gained since it was created.
+    pub fn open(path: &Path) -> Result<Database> {
+        let connection =
+            Connection::open(path).with_context(|| format!("Opening {}", path.display()))?;
+        let database = Database { connection };
+
+        database.connection.execute_batch(SCHEMA)?;
+        database.create_table("results", CSV_HEADER, "run_id, configuration_id")?;
+        database.create_table(
+            "samples",
+            SAMPLES_HEADER,
+            "run_id, configuration_id, repetition",
+        )?;
+
+        Ok(database)
+    }
+
+    /// Creates a table with a row per run and configuration and the columns of a CSV file
+    /// after its configuration columns, or adds the columns an existing table is missing.
+    fn create_table(&self, table: &str, header: &str, key: &str) -> Result<()> {
+        let columns = header
+            .split(',')
+            .skip(CONFIG_COLUMNS.len())
+            .collect::<Vec<_>>();
+
+        self.connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                run_id INTEGER NOT NULL REFERENCES runs (id),
+                configuration_id INTEGER NOT NULL REFERENCES configurations (id),
+                {},
+                PRIMARY KEY ({key})
+            )",
+            columns.join(", ")
+        ))?;
+
+        let existing = self
+            .connection
+            .prepare(&format!("SELECT name FROM pragma_table_info('{table}')"))?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for column in columns {
+            if !existing.iter().any(|existing| existing == column) {
+                self.connection
+                    .execute(&format!("ALTER TABLE {table} ADD COLUMN {column}"), [])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Opens the database at `path` for a run writing its results to `source`. Adds the run, or
+    /// when resuming, continues the run that was last writing to `source`.
+    pub fn open_run(
+        path: &Path,
+        source: &Path,
+        metadata: &RunMetadata,
+        resume: bool,
+    ) -> Result<(Database, i64)> {
+        let database = Database::open(path)?;
+
+        let run = match database.find_run(source)? {
+            Some(run) if resume => run,
+            _ => database.insert_run(source, metadata)?,
+        };
+
+        Ok((database, run))
+    }
+
+    /// Adds a run and returns its id.
+    pub fn insert_run(&self, source: &Path, metadata: &RunMetadata) -> Result<i64> {
+        self.connection
+            .execute(
+                "INSERT INTO runs (
+                    source, started_unix_secs, rustc, cargo, wasm_bindgen, wasm_opt, bevy,
+                    git_commit, git_dirty, os, cpu, cores, memory_bytes, browser, gpu, os_tag,
+                    design, schema_version
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+                params![
+                    source_name(source)?,
+                    metadata.started_unix_secs.map(|secs| secs as i64),
+                    metadata.rustc,
+                    metadata.cargo,
+                    metadata.wasm_bindgen,
+                    metadata.wasm_opt,
+                    metadata.bevy,
+                    metadata.git_commit,
+                    metadata.git_dirty,
+                    metadata.os,
+                    metadata.cpu,
+                    metadata.cores.map(|cores| cores as i64),
+                    metadata.memory_bytes.map(|bytes| bytes as i64),
+                    metadata.browser,
+                    metadata.gpu,
+                    metadata.os_tag,
+                    metadata
+                        .design
+                        .as_ref()
+                        .map(serde_json::to_string)
+                        .transpose()?,
+                    metadata.schema_version,
+                ],
+            )
+            .context("Adding run")?;
+
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// The most recent run written to or imported from `source`.
+    pub fn find_run(&self, source: &Path) -> Result<Option<i64>> {
+        Ok(self.connection.query_row(
+            "SELECT MAX(id) FROM runs WHERE source = ?1",
+            [source_name(source)?],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Deletes every run from `source` along with its results and samples, and returns how
+    /// many runs there were.
+    fn delete_runs(&self, source: &Path) -> Result<usize> {
+        let source = source_name(source)?;
+
+        for table in ["results", "samples"] {
+            self.connection.execute(
+                &format!(
+                    "DELETE FROM {table} WHERE run_id IN (SELECT id FROM runs WHERE source = ?1)"
+                ),
+                [&source],
+            )?;
+        }
+
+        Ok(self
+            .connection
+            .execute("DELETE FROM runs WHERE source = ?1", [&source])?)
+    }
+
+    /// Adds a row in the format of [`CSV_HEADER`] to a run, replacing any earlier row for the
+    /// same configuration.
+    pub fn insert_result(&self, run: i64, row: &str) -> Result<()> {
+        self.insert_row("results", CSV_HEADER, run, row)
+            .context("Adding result to database")
+    }
+
+    /// Adds a row in the format of [`SAMPLES_HEADER`] to a run, replacing any earlier sample
+    /// with the same configuration and repetition.
+    pub fn insert_sample(&self, run: i64, row: &str) -> Result<()> {
+        self.insert_row("samples", SAMPLES_HEADER, run, row)
+            .context("Adding sample to database")
+    }
+
+    fn insert_row(&self, table: &str, header: &str, run: i64, row: &str) -> Result<()> {
+        let columns = header.split(',').collect::<Vec<_>>();
+        let fields = row.split(',').collect::<Vec<_>>();
+        if fields.len() != columns.len() {
+            anyhow::bail!("Expected {} columns, found {}", columns.len(), fields.len());
+        }
+
+        let (config, values) = fields.split_at(CONFIG_COLUMNS.len());
+        let configuration = self.configuration_id(config)?;
+
+        let mut params = vec![Value::Integer(run), Value::Integer(configuration)];
+        params.extend(values.iter().map(|value| sql_value(value)));
+
+        self.connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {table} (run_id, configuration_id, {}) VALUES ({})",
+                columns[CONFIG_COLUMNS.len()..].join(", "),
+                vec!["?"; params.len()].join(", ")
+            ),
+            params_from_iter(params),
+        )?;
+
+        Ok(())
+    }
+
+    /// The id of a configuration, given the values of its [`CONFIG_COLUMNS`], adding it if
+    /// it's new.
+    fn configuration_id(&self, config: &[&str]) -> Result<i64> {
+        self.connection.execute(
+            "INSERT OR IGNORE INTO configurations
+                (opt_level, wasm_opt, lto, codegen_units, strip, panic)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params_from_iter(config),
+        )?;
+
+        Ok(self.connection.query_row(
+            "SELECT id FROM configurations WHERE opt_level = ?1 AND wasm_opt = ?2 AND lto = ?3
+                AND codegen_units = ?4 AND strip = ?5 AND panic = ?6",
+            params_from_iter(config),
+            |row| row.get(0),
+        )?)
+    }
+}
+
+/// Converts a CSV field to an SQL value. Empty fields and `NaN` become `NULL`.
+fn sql_value(field: &str) -> Value {
+    if let Ok(integer) = field.parse::<i64>() {
+        Value::Integer(integer)
+    } else if let Ok(real) = field.parse::<f64>() {
+        if real.is_nan() {
+            Value::Null
+        } else {
+            Value::Real(real)
+        }
+    } else {
+        match field {
+            "" => Value::Null,
+            "true" => Value::Integer(1),
+            "false" => Value::Integer(0),
+            _ => Value::Text(field.to_string()),
+        }
+    }
+}
+
+/// How `source` is stored in the `runs` table: its canonical path, so that a file reached
+/// through a relative path, a symlink or `..` is still recognized as the same run.
+fn source_name(source: &Path) -> Result<String> {
+    let source = match std::fs::canonicalize(source) {
+        Ok(source) => source,
+        Err(_) => std::path::absolute(source)?,
+    };
+
+    Ok(source.display().to_string())
+}
+
+/// Imports results files into the database, each as a run along with its samples and metadata
+/// files where they exist. Importing a file again replaces its earlier import.
+pub fn import(args: &ImportArgs) -> Result<()> {
+    let database = Database::open(&args.database)?;
+
+    for path in &args.inputs {
+        let metadata_path = metadata::metadata_path(path);
+        let mut metadata = if metadata_path.exists() {
+            RunMetadata::load(&metadata_path)?
+        } else {
+            metadata_from_name(path)
+        };
+        metadata.bevy = metadata.bevy.or(args.bevy.clone());
+        metadata.rustc = metadata.rustc.or(args.rustc.clone());
+        metadata.wasm_opt = metadata.wasm_opt.or(args.wasm_opt_version.clone());
+        // Rows are upgraded to the current schema as they're read.
+        metadata.schema_version = Some(SCHEMA_VERSION);
+
+        let records = results::read_records(path)?;
+
+        let samples_path = results::samples_path(path);
+        let samples = if samples_path.exists() {
+            results::read_samples(&samples_path).unwrap_or_else(|e| {
+                println!("Skipping samples: {e:#}");
+                vec![]
+            })
+        } else {
+            vec![]
+        };
+
+        let transaction = database.connection.unchecked_transaction()?;
+
+        if database.delete_runs(path)? > 0 {
+            println!("Replacing the earlier import of {}.", path.display());
+        }
+        let run = database.insert_run(path, &metadata)?;
+        for record in &records {
+            database.insert_result(run, &record.to_csv_row())?;
+        }
+        for sample in &samples {
+            database.insert_sample(run, sample)?;
+        }
+
+        transaction.commit()?;
+
+        println!(
+            "Imported {} results and {} samples from {}.",
+            records.len(),
+            samples.len(),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// What a results file name like `42674c1_win11_i713700KF_4080super` says about its run, for
+/// files from before metadata files were written.
+fn metadata_from_name(path: &Path) -> RunMetadata {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let parts = stem.split('_').collect::<Vec<_>>();
+
+    let [commit, os_tag, cpu, gpu] = parts[..] else {
+        return RunMetadata::default();
+    };
+    let (commit, dirty) = match commit.strip_suffix("-dirty") {
+        Some(commit) => (commit, true),
+        None => (commit, false),
+    };
+    if commit.len() < 7 || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return RunMetadata::default();
+    }
+
+    RunMetadata {
+        git_commit: Some(commit.to_string()),
+        git_dirty: Some(dirty),
+        os_tag: Some(os_tag.to_string()),
+        cpu: Some(cpu.to_string()),
+        gpu: Some(gpu.to_string()),
+        ..RunMetadata::default()
+    }
+}
+
+/// Prints the mean of a results column for each configuration, with a column for each value of
+/// a run field, like each bevy version.
+pub fn query(args: &QueryArgs) -> Result<()> {
+    if !args.database.exists() {
+        anyhow::bail!(
+            "{} doesn't exist. Write results to it with `--database` or `import`",
+            args.database.display()
+        );
+    }
+    let database = Database::open(&args.database)?;
+
+    let metrics = CSV_HEADER
+        .split(',')
+        .skip(CONFIG_COLUMNS.len())
+        .filter(|column| *column != "frame_histogram")
+        .collect::<Vec<_>>();
+    for column in args.column.columns() {
+        if !metrics.contains(&column.as_str()) {
+            anyhow::bail!(
+                "Unknown column {column}. Expected one of {}",
+                metrics.join(", ")
+            );
+        }
+    }
+
+    let value = args
+        .column
+        .columns()
+        .iter()
+        .map(|column| format!("results.{column}"))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let by = &args.by;
+
+    let mut statement = database.connection.prepare(&format!(
+        "SELECT runs.{by}, {}, AVG({value}), COUNT({value})
+            FROM results
+            JOIN runs ON runs.id = results.run_id
+            JOIN configurations AS c ON c.id = results.configuration_id
+            GROUP BY runs.{by}, c.id",
+        CONFIG_COLUMNS
+            .map(|column| format!("c.{column}"))
+            .join(", ")
+    ))?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                (1..=CONFIG_COLUMNS.len())
+                    .map(|i| row.get::<_, String>(i))
+                    .collect::<rusqlite::Result<Vec<_>>>()?,
+                row.get::<_, Option<f64>>(CONFIG_COLUMNS.len() + 1)?,
+                row.get::<_, i64>(CONFIG_COLUMNS.len() + 2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Pivot into a row per configuration and a column per group.
+
+    let mut groups = vec![];
+    let mut cells: HashMap<Config, BTreeMap<String, (f64, i64)>> = HashMap::new();
+
+    for (group, config, mean, count) in rows {
+        let config = results::parse_config(&config.join(","), CONFIG_COLUMNS.len())?;
+        let Some(mean) = mean else {
+            continue;
+        };
+        if !args.only.iter().all(|axes| axes.matches(&config)) {
+            continue;
+        }
+
+        let group = group.unwrap_or_else(|| "unknown".to_string());
+        if !groups.contains(&group) {
+            groups.push(group.clone());
+        }
+        cells
+            .entry(config)
+            .or_default()
+            .insert(group, (mean, count));
+    }
+
+    if cells.is_empty() {
+        anyhow::bail!(
+            "No results in {} match the filters",
+            args.database.display()
+        );
+    }
+
+    groups.sort_by_key(|group| (version_key(group), group.clone()));
+    let mut configs = cells.keys().copied().collect::<Vec<_>>();
+    configs.sort_by_key(|config| (config.cargo.name(), format!("{:?}", config.wasm_opt)));
+
+    let table = configs
+        .iter()
+        .map(|config| {
+            let mut row = vec![config.cargo.name(), format!("{:?}", config.wasm_opt)];
+            row.extend(groups.iter().map(|group| match cells[config].get(group) {
+                Some((mean, 1)) => args.column.format(*mean),
+                Some((mean, count)) => format!("{} ({count} runs)", args.column.format(*mean)),
+                None => String::new(),
+            }));
+            row
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = vec!["cargo".to_string(), "wasm_opt".to_string()];
+    header.extend(groups.iter().map(|group| format!("{by} {group}")));
+
+    println!("Mean {} by {by}", args.column.name());
+    println!();
+    println!(
+        "{}",
+        markdown_table(
+            &header.iter().map(String::as_str).collect::<Vec<_>>(),
+            &table
+        )
+    );
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Checks that the tools a run needs are installed, at versions that will work.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Result;
+
+use crate::metadata::{lock_version, version_of};
+use crate::WASM_OPT_COMMAND;
+
+/// Oldest `wasm-opt` that understands the features current rustc enables for wasm by default.
+const MIN_WASM_OPT_VERSION: u64 = 118;
+const TARGET: &str = "wasm32-unknown-unknown";
+
+/// Checks every dependency and prints a line for each, then fails with a list of fixes if any
+/// check failed. Expects to be run from the workspace directory.
+pub fn check_all_deps() -> Result<()> {
+    let mut fixes = vec![];
+
+    // Cargo
+
+    match version_of("cargo") {
+        Some(version) => pass("cargo", &version),
+        None => {
+            fail("cargo", "not found in PATH");
+            fixes.push("Install Rust from https://rustup.rs".to_string());
+        }
+    }
+
+    // Target
+
+    match target_installed(TARGET) {
+        Some(true) => pass(TARGET, "installed"),
+        Some(false) => {
+            fail(TARGET, "not installed");
+            fixes.push(format!("rustup target add {TARGET}"));
+        }
+        None => {
+            fail(TARGET, "couldn't find the rustc sysroot");
+            fixes.push("Install Rust from https://rustup.rs".to_string());
+        }
+    }
+
+    // wasm-bindgen, which must match the version of the crate exactly
+
+    if !Path::new("Cargo.lock").exists() {
+        println!("Generating Cargo.lock to check the wasm-bindgen version.");
+        let _ = Command::new("cargo").arg("generate-lockfile").status();
+    }
+
+    let locked = match lock_version("wasm-bindgen") {
+        Ok(locked) => locked,
+        Err(e) => {
+            eprintln!("Couldn't read wasm-bindgen's version from Cargo.lock: {e:#}");
+            None
+        }
+    };
+
+    match (version_of("wasm-bindgen"), locked) {
+        (None, locked) => {
+            fail("wasm-bindgen", "not found in PATH");
+            fixes.push(install_wasm_bindgen(locked.as_deref()));
+        }
+        (Some(installed), Some(locked)) if parse_version(&installed) != parse_version(&locked) => {
+            fail(
+                "wasm-bindgen",
+                &format!("{installed}, but Cargo.lock has wasm-bindgen {locked}"),
+            );
+            fixes.push(install_wasm_bindgen(Some(&locked)));
+        }
+        (Some(installed), Some(_)) => pass("wasm-bindgen", &installed),
+        (Some(installed), None) => pass(
+            "wasm-bindgen",
+            &format!("{installed} (not compared with Cargo.lock, which has no wasm-bindgen)"),
+        ),
+    }
+
+    // wasm-opt
+
+    match version_of(WASM_OPT_COMMAND) {
+        None => {
+            fail(WASM_OPT_COMMAND, "not found");
+            fixes.push(install_wasm_opt());
+        }
+        Some(installed) => match parse_version(&installed).and_then(|v| v.first().copied()) {
+            Some(version) if version >= MIN_WASM_OPT_VERSION => pass(WASM_OPT_COMMAND, &installed),
+            Some(_) => {
+                fail(
+                    WASM_OPT_COMMAND,
+                    &format!(
+                        "{installed}, but version {MIN_WASM_OPT_VERSION} or newer is required"
+                    ),
+                );
+                fixes.push(install_wasm_opt());
+            }
+            None => {
+                fail(
+                    WASM_OPT_COMMAND,
+                    &format!("unrecognized version {installed:?}"),
+                );
+                fixes.push(install_wasm_opt());
+            }
+        },
+    }
+
+    if !fixes.is_empty() {
+        eprintln!();
+        eprintln!("To fix:");
+        for fix in &fixes {
+            eprintln!("  {fix}");
+        }
+        anyhow::bail!("Missing or incompatible dependencies");
+    }
+
+    Ok(())
+}
+
+fn pass(dep: &str, detail: &str) {
+    println!("Checking {dep}: ✅ ({detail})");
+}
+
+fn fail(dep: &str, detail: &str) {
+    eprintln!("Checking {dep}: ❌ ({detail})");
+}
+
+fn install_wasm_bindgen(version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("cargo install wasm-bindgen-cli --version {version}"),
+        None => "cargo install wasm-bindgen-cli".to_string(),
+    }
+}
+
+fn install_wasm_opt() -> String {
+    format!(
+        "Install binaryen {MIN_WASM_OPT_VERSION} or newer from https://github.com/WebAssembly/binaryen/releases"
+    )
+}
+
+/// Whether the standard library for `target` is installed, or `None` if rustc couldn't be run.
+fn target_installed(target: &str) -> Option<bool> {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    Some(
+        Path::new(&sysroot)
+            .join("lib/rustlib")
+            .join(target)
+            .join("lib")
+            .is_dir(),
+    )
+}
+
+/// Finds the first version number in `text`, like `[0, 2, 100]` in `wasm-bindgen 0.2.100` or
+/// `[123]` in `wasm-opt version 123 (version_123)`.
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    text.split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .find_map(|word| {
+            word.trim_start_matches('v')
+                .split('.')
+                .map(|part| part.parse::<u64>().ok())
+                .collect::<Option<Vec<_>>>()
+        })
+}
@@ -0,0 +1,240 @@
+//! Experimental designs that pick a subset of the full factorial to run.
+//!
+//! Designs work on level indices: a point is one index per axis, into that axis's values.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+/// Gives up on a number of base factors after trying this many column assignments.
+const FRACTIONAL_SEARCH_BUDGET: usize = 200_000;
+/// Number of shuffles tried to find a Latin hypercube without repeated points.
+const LATIN_HYPERCUBE_ATTEMPTS: usize = 100;
+
+/// The designs that can be chosen on the command line.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum DesignKind {
+    /// Every configuration in the matrix.
+    Full,
+    /// A two-level fractional factorial, folded onto axes whose number of values isn't a
+    /// power of two.
+    Fractional,
+    /// A Latin hypercube of `--samples` points.
+    LatinHypercube,
+    /// `--samples` configurations picked uniformly at random.
+    Random,
+}
+
+/// Which configurations of the matrix were run, written into the run metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Design {
+    /// Every configuration.
+    Full,
+    /// A folded two-level fraction: a regular two-level fractional factorial in pseudo-factors,
+    /// with several pseudo-factors coding each axis that has more than two values.
+    ///
+    /// Only when every axis has a power of two values is the design orthogonal and of
+    /// resolution IV, so that main effects aren't aliased with each other or with two-factor
+    /// interactions. Otherwise the extra codes are folded back onto existing values, which
+    /// merges codes and leaves some values more often than others, so the design is neither,
+    /// and main effects are partly aliased.
+    Fractional {
+        /// Runs in the two-level design, before folding and removing duplicates.
+        runs: usize,
+        /// Whether no axis was folded. Missing in metadata from before it was recorded.
+        #[serde(default)]
+        orthogonal: bool,
+    },
+    /// `samples` points where every value of every axis appears about equally often.
+    ///
+    /// `samples` is the number of distinct points picked, which can be fewer than `--samples`.
+    LatinHypercube { samples: usize, seed: u64 },
+    /// `samples` configurations picked uniformly at random, without repeats.
+    ///
+    /// `samples` is the number picked, which is fewer than `--samples` when the matrix is
+    /// smaller.
+    Random { samples: usize, seed: u64 },
+}
+
+/// Builds a two-level design in pseudo-factors for axes with the given numbers of values, of
+/// resolution IV before folding. Returns its points, the number of runs in the two-level
+/// design, and whether it is orthogonal, which it isn't when an axis had to be folded.
+pub fn fractional_factorial(levels: &[usize]) -> Result<(Vec<Vec<usize>>, usize, bool)> {
+    // Code each axis as ceil(log2(levels)) pseudo-factors.
+
+    let mut factor_axes = vec![];
+    for (axis, &count) in levels.iter().enumerate() {
+        let bits = usize::BITS - count.saturating_sub(1).leading_zeros();
+        factor_axes.extend(std::iter::repeat_n(axis, bits as usize));
+    }
+
+    if factor_axes.is_empty() {
+        return Ok((vec![vec![0; levels.len()]], 1, true));
+    }
+
+    // Find the smallest full factorial in base factors whose columns can be assigned to the
+    // pseudo-factors without any defining word touching fewer than four axes. That needs the
+    // columns of any three axes to be independent, so there are at least as many base factors
+    // as the three axes with the most pseudo-factors have between them.
+
+    let mut axis_bits = (0..levels.len())
+        .map(|axis| factor_axes.iter().filter(|&&a| a == axis).count())
+        .collect::<Vec<_>>();
+    axis_bits.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut base_factors = axis_bits.iter().take(3).sum::<usize>();
+    let columns = loop {
+        if base_factors > factor_axes.len() {
+            anyhow::bail!("Couldn't find a resolution IV design for these axes");
+        }
+
+        let mut search = ColumnSearch {
+            factor_axes: &factor_axes,
+            base_factors,
+            columns: vec![],
+            budget: FRACTIONAL_SEARCH_BUDGET,
+        };
+        if search.assign() {
+            break search.columns;
+        }
+
+        base_factors += 1;
+    };
+
+    let runs = 1 << base_factors;
+
+    let points = (0..runs)
+        .map(|run: usize| {
+            let mut codes = vec![0; levels.len()];
+            for (factor, column) in columns.iter().enumerate() {
+                let axis = factor_axes[factor];
+                let bit = (run & column).count_ones() as usize % 2;
+                codes[axis] = codes[axis] << 1 | bit;
+            }
+
+            codes
+                .iter()
+                .zip(levels)
+                .map(|(code, count)| code % count.max(&1))
+                .collect()
+        })
+        .collect::<Vec<Vec<usize>>>();
+
+    let orthogonal = levels.iter().all(|count| count.is_power_of_two());
+
+    Ok((dedup(points), runs, orthogonal))
+}
+
+/// Backtracking search for an assignment of base factor interactions (as bit masks) to
+/// pseudo-factors.
+struct ColumnSearch<'a> {
+    /// The axis each pseudo-factor belongs to.
+    factor_axes: &'a [usize],
+    base_factors: usize,
+    columns: Vec<usize>,
+    budget: usize,
+}
+
+impl ColumnSearch<'_> {
+    fn assign(&mut self) -> bool {
+        let factor = self.columns.len();
+        if factor == self.factor_axes.len() {
+            return true;
+        }
+
+        // Odd-weight columns first, since those alone give a resolution IV design when every
+        // axis has two values.
+        let mut candidates = (1..1usize << self.base_factors)
+            .filter(|column| !self.columns.contains(column))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|column| (column.count_ones() % 2 == 0, *column));
+
+        for column in candidates {
+            if self.budget == 0 {
+                return false;
+            }
+            self.budget -= 1;
+
+            self.columns.push(column);
+            if self.is_resolution_iv() && self.assign() {
+                return true;
+            }
+            self.columns.pop();
+        }
+
+        false
+    }
+
+    /// Checks every defining word that includes the newest pseudo-factor.
+    fn is_resolution_iv(&self) -> bool {
+        let newest = self.columns.len() - 1;
+
+        for subset in 0..1usize << newest {
+            let mut product = self.columns[newest];
+            let mut axes = 1u64 << self.factor_axes[newest];
+
+            for factor in 0..newest {
+                if subset & 1 << factor != 0 {
+                    product ^= self.columns[factor];
+                    axes |= 1 << self.factor_axes[factor];
+                }
+            }
+
+            if product == 0 && axes.count_ones() < 4 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Picks `samples` points so that each value of each axis appears either
+/// `floor(samples / levels)` or `ceil(samples / levels)` times, preferring picks without
+/// repeated points.
+pub fn latin_hypercube(levels: &[usize], samples: usize, seed: u64) -> Vec<Vec<usize>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut best: Vec<Vec<usize>> = vec![];
+
+    for _ in 0..LATIN_HYPERCUBE_ATTEMPTS {
+        let columns = levels
+            .iter()
+            .map(|&count| {
+                let mut column = (0..samples).map(|i| i % count.max(1)).collect::<Vec<_>>();
+                column.shuffle(&mut rng);
+                column
+            })
+            .collect::<Vec<_>>();
+
+        let points = dedup(
+            (0..samples)
+                .map(|i| columns.iter().map(|column| column[i]).collect())
+                .collect(),
+        );
+
+        if points.len() > best.len() {
+            best = points;
+        }
+        if best.len() == samples {
+            break;
+        }
+    }
+
+    best
+}
+
+/// Removes repeated points, keeping the first of each.
+fn dedup(points: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+    let mut unique = vec![];
+    for point in points {
+        if !unique.contains(&point) {
+            unique.push(point);
+        }
+    }
+
+    unique
+}
@@ -1,23 +1,56 @@
-use std::fs::File;
-use std::io::{BufReader, Write};
-use std::process::{Stdio};
-use std::time::{Duration, Instant};
-use std::{path::Path, process::Command};
-
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use browser::find_browser;
+use build::Artifact;
+use cli::{BuildOnlyArgs, Cli, Command, MeasureArgs, MeasureDirArgs, RunArgs};
+use compress::Codec;
+use design::Design;
 use options::*;
 
 use anyhow::{Context, Result};
-use itertools::iproduct;
-use size::Size;
-use strum::IntoEnumIterator;
-
+use clap::Parser;
+use database::Database;
+use itertools::Itertools;
+use measure::{run_test, MeasureOptions, Session};
+use metadata::RunMetadata;
+use pipeline::Activity;
+use rand::seq::SliceRandom;
+use results::{Output, CSV_HEADER, SAMPLES_HEADER};
+use stats::Summary;
+
+mod analyze;
+mod browser;
+mod build;
+mod cli;
+mod compare;
+mod compress;
+mod database;
+mod deps;
+mod design;
+mod matrix;
+mod measure;
+mod metadata;
+mod migrate;
 mod options;
+mod pareto;
+mod pipeline;
+mod plan;
+mod plot;
+mod regression;
+mod report;
+mod results;
+mod search;
+mod server;
+mod stats;
+mod wasm_size;
 
 const PROFILE: &str = "bevy_wasm_bench";
 const NAME: &str = "bevy_wasm_bench";
 const OUT_DIR: &str = "web";
+const CSV_PATH: &str = "out.csv";
+/// Where `--auto-name` puts results.
+const RESULTS_DIR: &str = "results";
 
 #[cfg(target_os = "windows")]
 const WASM_OPT_COMMAND: &str = "./wasm-opt.exe";
@@ -25,257 +58,390 @@ const WASM_OPT_COMMAND: &str = "./wasm-opt.exe";
 const WASM_OPT_COMMAND: &str = "wasm-opt";
 
 fn main() -> Result<()> {
-    check_all_deps(&[
-        "cargo",
-        WASM_OPT_COMMAND,
-        "basic-http-server",
-        "wasm-bindgen",
-        "node",
-    ])?;
-    println!();
-
-    let workspace_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../");
-    let wasm_path = format!("target/wasm32-unknown-unknown/{}/bench.wasm", PROFILE);
-
-    std::env::set_current_dir(workspace_dir)?;
+    let cli = Cli::parse();
+
+    match cli.command.unwrap_or(Command::Run(cli.run)) {
+        Command::Run(args) => run(args),
+        Command::BuildOnly(args) => build_only(args),
+        Command::Measure(args) => measure_dir(args),
+        Command::Search(args) => search::search(args),
+        Command::Report(args) => report::report(&args),
+        Command::Compare(args) => compare::compare(&args),
+        Command::Analyze(args) => analyze::analyze(&args),
+        Command::Migrate(args) => migrate::migrate(&args),
+        Command::Import(args) => database::import(&args),
+        Command::Query(args) => database::query(&args),
+    }
+}
 
-    std::fs::create_dir_all("web/assets")?;
-    std::fs::copy("bench/assets/icon.png", "web/assets/icon.png")?;
+/// Builds and measures every configuration in the matrix.
+fn run(mut args: RunArgs) -> Result<()> {
+    args.output = std::path::absolute(&args.output)?;
+    args.matrix.matrix = std::path::absolute(&args.matrix.matrix)?;
+    args.database = args.database.map(std::path::absolute).transpose()?;
+    args.build.sizes = args.build.sizes.map(std::path::absolute).transpose()?;
+    enter_workspace()?;
 
-    let mut csv = File::create("out.csv")?;
-    writeln!(
-        csv,
-        "opt_level,wasm_opt,lto,codegen_units,strip,panic,build_time,wasm_opt_time,size,size_gzipped,frame_time"
-    )?;
+    let repetitions = args.measure.repetitions as usize;
 
-    let cargo_options_iter = iproduct!(
-        OptLevel::iter(),
-        Lto::iter(),
-        CodegenUnits::iter(),
-        Strip::iter(),
-        Panic::iter()
-    );
-    let num_options = cargo_options_iter.clone().count();
+    let (mut configs, design) = args.matrix.configs()?;
 
-    for (i, (opt_level, lto, codegen_units, strip, panic)) in cargo_options_iter.enumerate() {
-        println!("Cargo configuration {}/{}", i + 1, num_options);
+    if args.dry_run {
+        if design != Design::Full {
+            println!("Design: {design:?}");
+            println!();
+        }
 
-        // Create cargo options
+        if args.resume && args.output.exists() {
+            let done = results::completed(&args.output)?;
+            configs.retain(|config| !done.contains(config));
+            println!(
+                "Skipping {} configurations already in {}.",
+                done.len(),
+                args.output.display()
+            );
+            println!();
+        }
 
-        let options_toml = [
-            opt_level.option(),
-            lto.option(),
-            codegen_units.option(),
-            strip.option(),
-            panic.option(),
-        ]
-        .join("\n");
+        return plan::print_plan(&configs, repetitions);
+    }
 
-        std::fs::create_dir_all(".cargo")?;
-        std::fs::write(
-            ".cargo/config.toml",
-            format!(
-                "[profile.{}]\ninherits = \"release\"\n{}",
-                PROFILE, options_toml
-            ),
-        )?;
+    deps::check_all_deps()?;
+    let options = measure_options(&args.measure, OUT_DIR.into())?;
+    println!();
 
-        // Clean
+    copy_assets()?;
 
-        println!("Cleaning up.");
+    // Describe the environment
 
-        Command::new("cargo")
-            .arg("clean")
-            .output()
-            .context("Running cargo clean")?;
+    println!("Collecting environment metadata.");
+    let metadata = RunMetadata::collect(&options.browser, design);
 
-        // Build wasm
+    let csv_path = if args.auto_name {
+        Path::new(RESULTS_DIR).join(format!("{}.csv", metadata.result_name()))
+    } else {
+        args.output.clone()
+    };
+    let samples_path = results::samples_path(&csv_path);
+    let metadata_path = metadata::metadata_path(&csv_path);
 
+    let resume = args.resume && csv_path.exists();
+    let (csv, samples_csv) = if resume {
+        let (csv, done) = results::resume(&csv_path)?;
+        let samples_csv = results::resume_samples(&samples_path, &done)?;
+        let num_configs = configs.len();
+        configs.retain(|config| !done.contains(config));
         println!(
-            "Building with OptLevel::{:?}, Lto::{:?}, CodegenUnits::{:?}, Strip::{:?} Panic::{:?}",
-            opt_level, lto, codegen_units, strip, panic
+            "Resuming {}: {} of {} configurations already done.",
+            csv_path.display(),
+            num_configs - configs.len(),
+            num_configs
         );
 
-        let now = Instant::now();
-
-        let output = Command::new("cargo")
-            .arg("build")
-            .arg("-p")
-            .arg("bench")
-            .arg("--target=wasm32-unknown-unknown")
-            .args(["--profile", PROFILE])
-            .output()
-            .context("Building bench")?;
-        if !output.status.success() {
-            std::io::stdout().write_all(&output.stdout)?;
-            std::io::stderr().write_all(&output.stderr)?;
-            anyhow::bail!("Failed to build bench");
+        // Keep the metadata from when the run started, but point out anything that changed.
+        if metadata_path.exists() {
+            let changes = RunMetadata::load(&metadata_path)?.changes_from(&metadata);
+            if !changes.is_empty() {
+                println!(
+                    "The environment changed since the run started: {}",
+                    changes.join(", ")
+                );
+            }
+        } else {
+            metadata.write(&metadata_path)?;
         }
 
-        let build_time = now.elapsed();
-
-        for wasm_opt in WasmOpt::iter() {
-            // Bindgen
-
-            println!("Running bindgen.");
-
-            let output = Command::new("wasm-bindgen")
-                .args([
-                    "--out-name",
-                    NAME,
-                    "--out-dir",
-                    OUT_DIR,
-                    "--target",
-                    "web",
-                    &wasm_path,
-                ])
-                .output()
-                .context("Running wasm-bindgen")?;
-            if !output.status.success() {
-                std::io::stdout().write_all(&output.stdout)?;
-                std::io::stderr().write_all(&output.stderr)?;
-                anyhow::bail!("Failed to run wasm-bindgen");
+        println!();
+        (csv, samples_csv)
+    } else {
+        if let Some(dir) = csv_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        metadata.write(&metadata_path)?;
+        (
+            results::create(&csv_path, CSV_HEADER)?,
+            results::create(&samples_path, SAMPLES_HEADER)?,
+        )
+    };
+    let mut output = Output {
+        csv,
+        samples_csv,
+        database: args
+            .database
+            .as_ref()
+            .map(|path| Database::open_run(path, &csv_path, &metadata, resume))
+            .transpose()?,
+    };
+
+    let builds = group_builds(&configs);
+    let num_configs = configs.len();
+    let mut i = 0;
+
+    let activity = Activity::default();
+    let mut artifacts = vec![];
+
+    // With a single repetition, measure each configuration as soon as it's built. Otherwise
+    // build everything first, so that every session can be shuffled.
+
+    pipeline::run(
+        builds,
+        args.build.jobs,
+        &args.build.options(&csv_path),
+        &activity,
+        |artifact| {
+            i += 1;
+
+            println!();
+            println!(
+                "Configuration {}/{}: {} with WasmOpt::{:?}",
+                i,
+                num_configs,
+                artifact.config.cargo.name(),
+                artifact.config.wasm_opt
+            );
+            if artifact.contended {
+                println!("Build timings overlapped with other work.");
             }
 
-            println!("Running wasm-opt with WasmOpt::{:?}", wasm_opt);
+            if repetitions == 1 {
+                let session = measure(&artifact, &options, args.measure.retries, &activity)?;
+                output.write_sample(&artifact.config, 0, &session)?;
+                output.write_row(&artifact, &[session])?;
+            } else {
+                artifacts.push(artifact);
+            }
 
-            let bindgen_wasm_path = format!("{}/{}_bg.wasm", OUT_DIR, NAME);
+            Ok(())
+        },
+    )?;
 
-            let now = Instant::now();
+    // Take the repetitions in a shuffled order, so that drift over the course of the run is
+    // spread across configurations rather than concentrated in a few.
 
-            if wasm_opt.enabled() {
-                let output = Command::new(WASM_OPT_COMMAND)
-                    .args(wasm_opt.args())
-                    .arg(&bindgen_wasm_path)
-                    .args(["-o", &bindgen_wasm_path])
-                    .output()
-                    .context("Running wasm-opt")?;
-                if !output.status.success() {
-                    std::io::stdout().write_all(&output.stdout)?;
-                    std::io::stderr().write_all(&output.stderr)?;
-                    anyhow::bail!("Failed to run wasm-opt");
-                }
-            }
+    let mut order = (0..artifacts.len())
+        .flat_map(|i| std::iter::repeat_n(i, repetitions))
+        .collect::<Vec<_>>();
+    order.shuffle(&mut rand::thread_rng());
 
-            let wasm_opt_time = if wasm_opt.enabled() {
-                now.elapsed()
-            } else {
-                Duration::default()
-            };
+    let mut samples: HashMap<Config, Vec<Session>> = HashMap::new();
 
-            let attr = std::fs::metadata(&bindgen_wasm_path)?;
+    for (i, artifact_index) in order.iter().copied().enumerate() {
+        let artifact = &artifacts[artifact_index];
+        let config_samples = samples.entry(artifact.config).or_default();
 
-            // gzip to measure resulting filesize
+        println!();
+        println!(
+            "Session {}/{}: {} with WasmOpt::{:?} (sample {}/{})",
+            i + 1,
+            order.len(),
+            artifact.config.cargo.name(),
+            artifact.config.wasm_opt,
+            config_samples.len() + 1,
+            repetitions
+        );
 
-            println!("Compressing.");
+        let session = measure(artifact, &options, args.measure.retries, &activity)?;
+        output.write_sample(&artifact.config, config_samples.len(), &session)?;
+        config_samples.push(session);
 
-            let compressed_path = format!("{}.gz", bindgen_wasm_path);
+        if config_samples.len() == repetitions {
+            print_summary(config_samples);
+            output.write_row(artifact, config_samples)?;
+        }
+    }
 
-            compress(&bindgen_wasm_path, &compressed_path).context("Compressing wasm")?;
+    Ok(())
+}
 
-            let attr_gz = std::fs::metadata(format!("{}.gz", bindgen_wasm_path))?;
+/// Builds every configuration in the matrix and reports its size, without measuring it.
+fn build_only(mut args: BuildOnlyArgs) -> Result<()> {
+    args.matrix.matrix = std::path::absolute(&args.matrix.matrix)?;
+    args.build.sizes = args.build.sizes.map(std::path::absolute).transpose()?;
+    enter_workspace()?;
 
-            println!(
-                "{} ({} gzipped)",
-                Size::from_bytes(attr.len()),
-                Size::from_bytes(attr_gz.len())
-            );
-            println!("{:.2?} (+{:.2?} wasm-opt)", build_time, wasm_opt_time);
+    let (configs, _) = args.matrix.configs()?;
 
-            println!("Testing runtime performance.");
+    deps::check_all_deps()?;
+    println!();
 
-            let frame_time = retry(run_test, 3)?;
+    let activity = Activity::default();
+    let mut table = vec![];
+
+    pipeline::run(
+        group_builds(&configs),
+        args.build.jobs,
+        &args.build.options(Path::new(CSV_PATH)),
+        &activity,
+        |artifact| {
+            table.push(vec![
+                artifact.config.cargo.name(),
+                format!("{:?}", artifact.config.wasm_opt),
+                report::format_size(Some(artifact.size as f64)),
+                report::format_size(
+                    artifact
+                        .compressed_size(Codec::Gzip)
+                        .map(|size| size as f64),
+                ),
+                report::format_secs(Some(artifact.build_time.as_secs_f64())),
+                report::format_secs(Some(artifact.wasm_opt_time.as_secs_f64())),
+            ]);
+            Ok(())
+        },
+    )?;
 
-            println!();
+    println!();
+    println!(
+        "{}",
+        report::markdown_table(
+            &[
+                "cargo",
+                "wasm_opt",
+                "size",
+                "size_gzipped",
+                "build_time",
+                "wasm_opt_time"
+            ],
+            &table
+        )
+    );
 
-            writeln!(
-                csv,
-                "{:?},{:?},{:?},{:?},{:?},{:?},{},{},{},{},{}",
-                opt_level,
-                wasm_opt,
-                lto,
-                codegen_units,
-                strip,
-                panic,
-                build_time.as_secs_f32(),
-                wasm_opt_time.as_secs_f32(),
-                attr.len(),
-                attr_gz.len(),
-                frame_time
-            )
-            .context("Writing to out.csv")?;
-        }
+    Ok(())
+}
+
+/// Measures whatever is built into a web directory, without building anything.
+fn measure_dir(mut args: MeasureDirArgs) -> Result<()> {
+    args.dir = std::path::absolute(&args.dir)?;
+    enter_workspace()?;
+
+    let options = measure_options(&args.measure, args.dir.clone())?;
+    println!();
+
+    let mut sessions = vec![];
+    for i in 0..args.measure.repetitions {
+        println!("Session {}/{}", i + 1, args.measure.repetitions);
+        sessions.push(retry(|| run_test(&options), args.measure.retries)?);
+    }
+
+    if sessions.len() > 1 {
+        print_summary(&sessions);
     }
 
     Ok(())
 }
 
-fn run_test() -> Result<f32, anyhow::Error> {
-    let mut h = Command::new("basic-http-server")
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .arg("web")
-        .arg("-a")
-        .arg("127.0.0.1:1334")
-        .spawn()
-        .context("Starting web server")?;
+/// Groups configurations sharing cargo options into a single build each.
+fn group_builds(configs: &[Config]) -> Vec<(CargoOptions, Vec<WasmOpt>)> {
+    configs
+        .iter()
+        .chunk_by(|config| config.cargo)
+        .into_iter()
+        .map(|(cargo, group)| {
+            (
+                cargo,
+                group.map(|config| config.wasm_opt).collect::<Vec<_>>(),
+            )
+        })
+        .collect()
+}
 
-    let puppeteer_out = Command::new("node")
-        .arg("index.js")
-        .output()
-        .context("Running puppeteer")?;
+fn print_summary(sessions: &[Session]) {
+    let summary = Summary::new(
+        &sessions
+            .iter()
+            .map(|session| session.frames.average)
+            .collect::<Vec<_>>(),
+    );
+    println!(
+        "{:.2}ms ± {:.2}ms (95% CI {:.2}..{:.2})",
+        summary.mean, summary.stddev, summary.ci_low, summary.ci_high
+    );
+}
 
-    let frame_time = String::from_utf8(puppeteer_out.stdout)
-        .context("Building utf8 from puppeteer output")?
-        .trim()
-        .parse::<f32>()
-        .context("Parsing puppeteer output")?;
+/// Runs from the workspace directory, where the bench, matrix and results live.
+fn enter_workspace() -> Result<()> {
+    let workspace_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../");
 
-    println!("{:2}ms", frame_time);
+    std::env::set_current_dir(workspace_dir).context("Entering the workspace directory")
+}
 
-    h.kill().context("Killing web server")?;
+/// Copies the assets the bench page needs into the web directory.
+fn copy_assets() -> Result<()> {
+    std::fs::create_dir_all("web/assets")?;
+    std::fs::copy("bench/assets/icon.png", "web/assets/icon.png")?;
 
-    Ok(frame_time)
+    Ok(())
 }
 
-fn check_all_deps(deps: &[&str]) -> Result<()> {
-    let mut failed = false;
-
-    for dep in deps {
-        let output = Command::new(dep).arg("--help").output();
+fn measure_options(args: &MeasureArgs, dir: PathBuf) -> Result<MeasureOptions> {
+    Ok(MeasureOptions {
+        browser: check_browser(args.browser.as_deref())?,
+        dir,
+        precompressed: args.precompressed,
+        timeout: args.timeout(),
+    })
+}
 
-        match output {
-            Ok(_output) => {
-                println!("Checking for {dep} in PATH: ✅");
-            }
-            Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound) => {
-                eprintln!("Checking for {dep} in PATH: ❌");
-                failed = true;
-            }
-            Err(e) => {
-                eprintln!("Checking for {dep} in PATH: ❌");
-                panic!("Unknown IO error: {:?}", e);
-            }
+/// Copies an artifact into the web directory and measures its frame time in the browser.
+fn measure(
+    artifact: &Artifact,
+    options: &MeasureOptions,
+    retries: usize,
+    activity: &Activity,
+) -> Result<Session> {
+    // Remove the previous build's files first, since it may have had other compressed
+    // variants.
+    for entry in std::fs::read_dir(&options.dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(NAME) {
+            std::fs::remove_file(entry.path())?;
         }
     }
+    copy_dir(&artifact.dir, &options.dir).context("Copying build to web")?;
 
-    if failed {
-        anyhow::bail!("Missing required program(s)".to_string());
-    }
+    println!("Testing runtime performance.");
 
-    Ok(())
+    let measurement = activity.start_measurement();
+    let mut session = retry(|| run_test(options), retries)?;
+    session.contended = measurement.finish();
+
+    Ok(session)
 }
 
-fn compress<P>(input_path: P, output_path: P) -> Result<()>
-where
-    P: AsRef<Path>,
-{
-    let mut input = BufReader::new(File::open(input_path)?);
-    let output = File::create(output_path)?;
-    let mut encoder = GzEncoder::new(output, Compression::default());
+/// Finds the browser to run tests in, either from `--browser <path>` or a default install.
+fn check_browser(path: Option<&Path>) -> Result<PathBuf> {
+    let browser = match path {
+        Some(path) => Some(path.to_path_buf()).filter(|path| path.exists()),
+        None => find_browser(),
+    };
+
+    match browser {
+        Some(path) => {
+            println!("Checking for Chromium: ✅ ({})", path.display());
+            Ok(path)
+        }
+        None => {
+            eprintln!("Checking for Chromium: ❌");
+            anyhow::bail!(
+                "Missing Chromium. Install Chrome or Chromium, or pass --browser <path>."
+            );
+        }
+    }
+}
 
-    std::io::copy(&mut input, &mut encoder)?;
-    let _ = encoder.finish()?;
+/// Copies the files in `from` into `to`, recursively.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            std::fs::copy(entry.path(), to)?;
+        }
+    }
 
     Ok(())
 }
@@ -297,4 +463,4 @@ where
             Err(e) => return Err(e),
         }
     }
-}
\ No newline at end of file
+}
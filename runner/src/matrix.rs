@@ -0,0 +1,412 @@
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use itertools::{iproduct, Itertools};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+
+use crate::design::{self, Design, DesignKind};
+use crate::options::*;
+
+pub const MATRIX_PATH: &str = "bench-matrix.toml";
+
+/// Either a single fixed value or a list of values for one axis.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AxisValues<T> {
+    One(T),
+    Many(Vec<T>),
+}
+impl<T: Copy + PartialEq> AxisValues<T> {
+    fn values(&self) -> Vec<T> {
+        match self {
+            Self::One(value) => vec![*value],
+            Self::Many(values) => values.clone(),
+        }
+    }
+    fn contains(&self, value: &T) -> bool {
+        match self {
+            Self::One(v) => v == value,
+            Self::Many(values) => values.contains(value),
+        }
+    }
+}
+
+/// Values for each axis in `options.rs`.
+///
+/// In `[axes]`, a missing axis means "every variant". In an `[[exclude]]` rule,
+/// a missing axis matches anything.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Axes {
+    pub opt_level: Option<AxisValues<OptLevel>>,
+    pub wasm_opt: Option<AxisValues<WasmOpt>>,
+    pub lto: Option<AxisValues<Lto>>,
+    pub codegen_units: Option<AxisValues<CodegenUnits>>,
+    pub strip: Option<AxisValues<Strip>>,
+    pub panic: Option<AxisValues<Panic>>,
+}
+impl Axes {
+    fn is_empty(&self) -> bool {
+        self.opt_level.is_none()
+            && self.wasm_opt.is_none()
+            && self.lto.is_none()
+            && self.codegen_units.is_none()
+            && self.strip.is_none()
+            && self.panic.is_none()
+    }
+
+    /// Parses a command line filter like `opt_level=S,Z` into an `Axes` with only that axis set.
+    pub fn parse_filter(filter: &str) -> Result<Self> {
+        let (axis, values) = filter.split_once('=').with_context(|| {
+            format!("Expected <axis>=<values>, like opt_level=S,Z, found {filter}")
+        })?;
+        let values = values.split(',').map(str::trim).collect::<Vec<_>>();
+
+        let mut axes = Axes::default();
+        match axis.trim() {
+            "opt_level" => axes.opt_level = Some(parse_variants(axis, &values)?),
+            "wasm_opt" => axes.wasm_opt = Some(parse_variants(axis, &values)?),
+            "lto" => axes.lto = Some(parse_variants(axis, &values)?),
+            "codegen_units" => axes.codegen_units = Some(parse_variants(axis, &values)?),
+            "strip" => axes.strip = Some(parse_variants(axis, &values)?),
+            "panic" => axes.panic = Some(parse_variants(axis, &values)?),
+            _ => anyhow::bail!(
+                "Unknown axis {axis}. Expected one of opt_level, wasm_opt, lto, codegen_units, strip, panic"
+            ),
+        }
+
+        Ok(axes)
+    }
+
+    /// Returns true if every axis specified in this rule contains the config's value.
+    pub fn matches(&self, config: &Config) -> bool {
+        fn axis_matches<T: Copy + PartialEq>(axis: &Option<AxisValues<T>>, value: &T) -> bool {
+            axis.as_ref().is_none_or(|axis| axis.contains(value))
+        }
+
+        axis_matches(&self.opt_level, &config.cargo.opt_level)
+            && axis_matches(&self.wasm_opt, &config.wasm_opt)
+            && axis_matches(&self.lto, &config.cargo.lto)
+            && axis_matches(&self.codegen_units, &config.cargo.codegen_units)
+            && axis_matches(&self.strip, &config.cargo.strip)
+            && axis_matches(&self.panic, &config.cargo.panic)
+    }
+}
+
+/// A declarative description of the benchmark sweep, usually loaded from `bench-matrix.toml`.
+///
+/// ```toml
+/// [axes]
+/// opt_level = ["S", "Z"]
+/// lto = "Fat"
+///
+/// [[exclude]]
+/// opt_level = "Z"
+/// wasm_opt = ["None", "Three"]
+/// ```
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Matrix {
+    #[serde(default)]
+    pub axes: Axes,
+    #[serde(default)]
+    pub exclude: Vec<Axes>,
+}
+impl Matrix {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        let matrix: Matrix =
+            toml::from_str(&contents).with_context(|| format!("Parsing {}", path.display()))?;
+
+        Ok(matrix)
+    }
+
+    /// Loads the matrix at `path` if it exists, or falls back to the full sweep.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Narrows the axes to the values in each of `only`, and adds each of `exclude` as an
+    /// exclusion rule.
+    pub fn filter(&mut self, only: &[Axes], exclude: &[Axes]) -> Result<()> {
+        for filter in only {
+            narrow("opt_level", &mut self.axes.opt_level, &filter.opt_level)?;
+            narrow("wasm_opt", &mut self.axes.wasm_opt, &filter.wasm_opt)?;
+            narrow("lto", &mut self.axes.lto, &filter.lto)?;
+            narrow(
+                "codegen_units",
+                &mut self.axes.codegen_units,
+                &filter.codegen_units,
+            )?;
+            narrow("strip", &mut self.axes.strip, &filter.strip)?;
+            narrow("panic", &mut self.axes.panic, &filter.panic)?;
+        }
+
+        self.exclude.extend(exclude.iter().cloned());
+
+        Ok(())
+    }
+
+    /// Validates the matrix and expands it into the list of configurations to run.
+    ///
+    /// Configurations are ordered so that those sharing cargo options are adjacent,
+    /// with `wasm_opt` varying fastest.
+    pub fn expand(&self) -> Result<Vec<Config>> {
+        let levels = self.levels()?;
+
+        for (i, rule) in self.exclude.iter().enumerate() {
+            if rule.is_empty() {
+                anyhow::bail!(
+                    "Exclusion rule {} has no axes and would exclude everything",
+                    i + 1
+                );
+            }
+        }
+
+        let configs = iproduct!(
+            levels.opt_levels,
+            levels.ltos,
+            levels.codegen_units,
+            levels.strips,
+            levels.panics,
+            levels.wasm_opts
+        )
+        .map(
+            |(opt_level, lto, codegen_units, strip, panic, wasm_opt)| Config {
+                cargo: CargoOptions {
+                    opt_level,
+                    lto,
+                    codegen_units,
+                    strip,
+                    panic,
+                },
+                wasm_opt,
+            },
+        )
+        .filter(|config| !self.exclude.iter().any(|rule| rule.matches(config)))
+        .collect::<Vec<_>>();
+
+        if configs.is_empty() {
+            anyhow::bail!("Benchmark matrix contains no configurations");
+        }
+
+        Ok(configs)
+    }
+
+    /// Expands the matrix and keeps only the configurations chosen by a design, in the same
+    /// order as `expand`. Chosen configurations that are excluded by a rule are dropped.
+    ///
+    /// `samples` is required by the Latin hypercube and random designs.
+    pub fn expand_design(
+        &self,
+        kind: DesignKind,
+        samples: Option<usize>,
+        seed: u64,
+    ) -> Result<(Vec<Config>, Design)> {
+        let mut configs = self.expand()?;
+        let levels = self.levels()?;
+
+        let samples = || {
+            samples.with_context(|| {
+                format!(
+                    "--design {} needs --samples",
+                    kind.to_possible_value().unwrap().get_name()
+                )
+            })
+        };
+
+        let (points, design) = match kind {
+            DesignKind::Full => return Ok((configs, Design::Full)),
+            DesignKind::Random => {
+                let samples = samples()?;
+                let chosen = configs
+                    .choose_multiple(&mut StdRng::seed_from_u64(seed), samples)
+                    .copied()
+                    .collect::<HashSet<_>>();
+                configs.retain(|config| chosen.contains(config));
+                warn_short(samples, chosen.len());
+
+                return Ok((
+                    configs,
+                    Design::Random {
+                        samples: chosen.len(),
+                        seed,
+                    },
+                ));
+            }
+            DesignKind::Fractional => {
+                let (points, runs, orthogonal) = design::fractional_factorial(&levels.counts())?;
+                (points, Design::Fractional { runs, orthogonal })
+            }
+            DesignKind::LatinHypercube => {
+                let samples = samples()?;
+                let points = design::latin_hypercube(&levels.counts(), samples, seed);
+                warn_short(samples, points.len());
+
+                let samples = points.len();
+                (points, Design::LatinHypercube { samples, seed })
+            }
+        };
+
+        let chosen = points
+            .iter()
+            .map(|point| levels.config(point))
+            .collect::<HashSet<_>>();
+        configs.retain(|config| chosen.contains(config));
+
+        if configs.is_empty() {
+            anyhow::bail!("Every configuration chosen by the design is excluded");
+        }
+
+        Ok((configs, design))
+    }
+
+    fn levels(&self) -> Result<Levels> {
+        Ok(Levels {
+            opt_levels: axis_values("opt_level", &self.axes.opt_level)?,
+            ltos: axis_values("lto", &self.axes.lto)?,
+            codegen_units: axis_values("codegen_units", &self.axes.codegen_units)?,
+            strips: axis_values("strip", &self.axes.strip)?,
+            panics: axis_values("panic", &self.axes.panic)?,
+            wasm_opts: axis_values("wasm_opt", &self.axes.wasm_opt)?,
+        })
+    }
+}
+
+/// The values of each axis, in the order `expand` varies them.
+struct Levels {
+    opt_levels: Vec<OptLevel>,
+    ltos: Vec<Lto>,
+    codegen_units: Vec<CodegenUnits>,
+    strips: Vec<Strip>,
+    panics: Vec<Panic>,
+    wasm_opts: Vec<WasmOpt>,
+}
+impl Levels {
+    fn counts(&self) -> Vec<usize> {
+        vec![
+            self.opt_levels.len(),
+            self.ltos.len(),
+            self.codegen_units.len(),
+            self.strips.len(),
+            self.panics.len(),
+            self.wasm_opts.len(),
+        ]
+    }
+
+    /// The configuration at a design point, which holds an index into each axis.
+    fn config(&self, point: &[usize]) -> Config {
+        Config {
+            cargo: CargoOptions {
+                opt_level: self.opt_levels[point[0]],
+                lto: self.ltos[point[1]],
+                codegen_units: self.codegen_units[point[2]],
+                strip: self.strips[point[3]],
+                panic: self.panics[point[4]],
+            },
+            wasm_opt: self.wasm_opts[point[5]],
+        }
+    }
+}
+
+/// Points out when a design picked fewer configurations than `--samples` asked for.
+fn warn_short(requested: usize, picked: usize) {
+    if picked < requested {
+        println!("The design only has {picked} distinct configurations of --samples {requested}.");
+    }
+}
+
+fn axis_values<T>(name: &str, axis: &Option<AxisValues<T>>) -> Result<Vec<T>>
+where
+    T: IntoEnumIterator + Copy + PartialEq + Eq + Hash + Debug,
+{
+    let Some(axis) = axis else {
+        return Ok(T::iter().collect());
+    };
+
+    let values = axis.values();
+
+    if values.is_empty() {
+        anyhow::bail!("Axis {name} has no values");
+    }
+
+    let mut seen = HashSet::new();
+    for value in &values {
+        if !seen.insert(value) {
+            anyhow::bail!("Axis {name} lists {value:?} more than once");
+        }
+    }
+
+    Ok(values)
+}
+
+/// Parses variant names for an axis, ignoring case.
+fn parse_variants<T>(axis: &str, values: &[&str]) -> Result<AxisValues<T>>
+where
+    T: IntoEnumIterator + Debug,
+{
+    let variants = values
+        .iter()
+        .map(|value| {
+            T::iter()
+                .find(|variant| format!("{variant:?}").eq_ignore_ascii_case(value))
+                .with_context(|| {
+                    format!(
+                        "Unknown {axis} {value}. Expected one of {}",
+                        T::iter().map(|variant| format!("{variant:?}")).join(", ")
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(AxisValues::Many(variants))
+}
+
+/// Removes the values of `axis` that aren't in `filter`. A missing axis starts out with every
+/// variant.
+fn narrow<T>(
+    name: &str,
+    axis: &mut Option<AxisValues<T>>,
+    filter: &Option<AxisValues<T>>,
+) -> Result<()>
+where
+    T: IntoEnumIterator + Copy + PartialEq + Debug,
+{
+    let Some(filter) = filter else {
+        return Ok(());
+    };
+
+    let values = match axis {
+        Some(axis) => axis.values(),
+        None => T::iter().collect(),
+    };
+    let narrowed = values
+        .into_iter()
+        .filter(|value| filter.contains(value))
+        .collect::<Vec<_>>();
+
+    if narrowed.is_empty() {
+        anyhow::bail!(
+            "Filtering {name} to {:?} leaves no values from the matrix",
+            filter.values()
+        );
+    }
+
+    *axis = Some(AxisValues::Many(narrowed));
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! Runs the bench in the browser and collects its measurements.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use protocol::BenchResult;
+use serde_json::Value;
+
+use crate::browser::{Browser, PageEvent};
+use crate::server::Server;
+
+pub struct MeasureOptions {
+    /// The Chromium executable to measure in.
+    pub browser: PathBuf,
+    /// Directory holding the bench page and the build to measure.
+    pub dir: PathBuf,
+    /// Serve precompressed files with `Content-Encoding`.
+    pub precompressed: bool,
+    /// How long to wait for the bench to report a result.
+    pub timeout: Duration,
+}
+
+/// Everything measured in one browser session.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub frames: FrameStats,
+    pub load: LoadTimes,
+    /// Whether a build or wasm-opt pass was still running during the session, which skews its
+    /// frame times.
+    pub contended: bool,
+}
+
+impl Session {
+    /// Combines several sessions of the same build. See [`FrameStats::combine`] and
+    /// [`LoadTimes::combine`].
+    pub fn combine(sessions: &[Session]) -> Session {
+        Session {
+            frames: FrameStats::combine(
+                &sessions
+                    .iter()
+                    .map(|s| s.frames.clone())
+                    .collect::<Vec<_>>(),
+            ),
+            load: LoadTimes::combine(&sessions.iter().map(|s| s.load).collect::<Vec<_>>()),
+            contended: sessions.iter().any(|s| s.contended),
+        }
+    }
+}
+
+/// How long the page took to get going, in ms. Missing times are `NaN`.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadTimes {
+    /// Time to fetch the wasm file, from Resource Timing.
+    pub download: f64,
+    /// Time `WebAssembly.compileStreaming` took after the download finished, since compiling
+    /// overlaps with the download.
+    pub compile: f64,
+    /// Time for wasm-bindgen's `init` to instantiate the module and run `main`.
+    pub instantiate: f64,
+    /// Time from navigation until the `Startup` schedule had run.
+    pub startup: f64,
+    /// Time from navigation until the first frame had been rendered.
+    pub first_frame: f64,
+}
+
+impl LoadTimes {
+    /// Reads the times recorded by the page's loader script and the bench.
+    fn new(page_times: &Value, result: &BenchResult) -> Self {
+        let page_time = |name: &str| page_times[name].as_f64().unwrap_or(f64::NAN);
+
+        LoadTimes {
+            download: page_time("download_ms"),
+            compile: page_time("compile_ms"),
+            instantiate: page_time("instantiate_ms"),
+            startup: result.load.startup_ms.unwrap_or(f64::NAN),
+            first_frame: result.load.first_frame_ms.unwrap_or(f64::NAN),
+        }
+    }
+
+    /// Whether every time is missing.
+    pub fn is_empty(&self) -> bool {
+        [
+            self.download,
+            self.compile,
+            self.instantiate,
+            self.startup,
+            self.first_frame,
+        ]
+        .iter()
+        .all(|time| time.is_nan())
+    }
+
+    /// Averages load times across sessions.
+    pub fn combine(sessions: &[LoadTimes]) -> LoadTimes {
+        let n = sessions.len() as f64;
+        let mean = |f: fn(&LoadTimes) -> f64| sessions.iter().map(f).sum::<f64>() / n;
+
+        LoadTimes {
+            download: mean(|s| s.download),
+            compile: mean(|s| s.compile),
+            instantiate: mean(|s| s.instantiate),
+            startup: mean(|s| s.startup),
+            first_frame: mean(|s| s.first_frame),
+        }
+    }
+}
+
+/// Frame time measurements from one browser session.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    /// Average frame time in ms.
+    pub average: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    /// Number of frames that took longer than the frame budget.
+    pub over_budget: u64,
+    /// Number of frames measured.
+    pub frames: u64,
+    /// Frame counts per histogram bucket, labelled like `<16.7` or `>=100` (in ms).
+    pub histogram: Vec<(String, u64)>,
+}
+
+impl From<&BenchResult> for FrameStats {
+    fn from(result: &BenchResult) -> Self {
+        let frames = &result.frames;
+
+        FrameStats {
+            average: frames.average_ms as f64,
+            p50: frames.p50_ms as f64,
+            p90: frames.p90_ms as f64,
+            p95: frames.p95_ms as f64,
+            p99: frames.p99_ms as f64,
+            max: frames.max_ms as f64,
+            over_budget: frames.over_budget,
+            frames: frames.count,
+            histogram: frames
+                .histogram
+                .iter()
+                .map(|bucket| (bucket.label(), bucket.count))
+                .collect(),
+        }
+    }
+}
+
+impl FrameStats {
+    /// Combines stats from several sessions of the same build.
+    ///
+    /// Frame time percentiles are averaged across sessions, while frame counts and the
+    /// histogram are summed.
+    pub fn combine(sessions: &[FrameStats]) -> FrameStats {
+        let n = sessions.len() as f64;
+        let mean = |f: fn(&FrameStats) -> f64| sessions.iter().map(f).sum::<f64>() / n;
+
+        let mut histogram: Vec<(String, u64)> = vec![];
+        for session in sessions {
+            for (bucket, count) in &session.histogram {
+                match histogram.iter_mut().find(|(b, _)| b == bucket) {
+                    Some((_, total)) => *total += count,
+                    None => histogram.push((bucket.clone(), *count)),
+                }
+            }
+        }
+
+        FrameStats {
+            average: mean(|s| s.average),
+            p50: mean(|s| s.p50),
+            p90: mean(|s| s.p90),
+            p95: mean(|s| s.p95),
+            p99: mean(|s| s.p99),
+            max: mean(|s| s.max),
+            over_budget: sessions.iter().map(|s| s.over_budget).sum(),
+            frames: sessions.iter().map(|s| s.frames).sum(),
+            histogram,
+        }
+    }
+
+    /// The histogram in a compact, comma-free form like `<4:0;<8:12;...`.
+    pub fn histogram_string(&self) -> String {
+        self.histogram
+            .iter()
+            .map(|(bucket, count)| format!("{bucket}:{count}"))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
+
+/// Serves the web directory and measures the bench in a fresh browser session.
+pub fn run_test(options: &MeasureOptions) -> Result<Session> {
+    let server =
+        Server::start(&options.dir, options.precompressed).context("Starting web server")?;
+
+    let result = measure_session(options, &server.url());
+
+    server.stop().context("Stopping web server")?;
+
+    let session = result?;
+    let stats = &session.frames;
+
+    println!(
+        "{:.2}ms (p50 {:.2}ms, p99 {:.2}ms, {}/{} frames over budget), first frame after {:.0}ms",
+        stats.average,
+        stats.p50,
+        stats.p99,
+        stats.over_budget,
+        stats.frames,
+        session.load.first_frame
+    );
+
+    Ok(session)
+}
+
+fn measure_session(options: &MeasureOptions, url: &str) -> Result<Session> {
+    let mut browser = Browser::launch(&options.browser)?;
+    let session = browser.open_page(url, 1920, 1080)?;
+
+    let deadline = Instant::now() + options.timeout;
+
+    let result = loop {
+        match browser.next_page_event(&session, deadline)? {
+            Some(PageEvent::Console(text)) => {
+                if let Some(result) = BenchResult::from_message(&text) {
+                    break result?;
+                }
+            }
+            Some(PageEvent::Exception(text)) => {
+                anyhow::bail!("Uncaught exception in bench: {text}");
+            }
+            None => anyhow::bail!("Timed out waiting for a bench result"),
+        }
+    };
+
+    let page_times = browser.evaluate(&session, "window.benchLoadTimes")?;
+
+    browser.close()?;
+
+    Ok(Session {
+        frames: FrameStats::from(&result),
+        load: LoadTimes::new(&page_times, &result),
+        contended: false,
+    })
+}
@@ -0,0 +1,382 @@
+//! Describes the toolchain, machine and browser a run was made with, and the design it followed.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::browser::Browser;
+use crate::design::Design;
+use crate::results::SCHEMA_VERSION;
+use crate::WASM_OPT_COMMAND;
+
+/// Written next to the results CSV, so that results can be compared knowing what produced them.
+///
+/// Anything that couldn't be determined is `None`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RunMetadata {
+    /// When the run started, in seconds since the Unix epoch.
+    pub started_unix_secs: Option<u64>,
+    pub rustc: Option<String>,
+    pub cargo: Option<String>,
+    pub wasm_bindgen: Option<String>,
+    pub wasm_opt: Option<String>,
+    /// Version of the `bevy` crate in `Cargo.lock`.
+    pub bevy: Option<String>,
+    pub git_commit: Option<String>,
+    /// Whether tracked files had uncommitted changes.
+    pub git_dirty: Option<bool>,
+    pub os: Option<String>,
+    pub cpu: Option<String>,
+    pub cores: Option<usize>,
+    pub memory_bytes: Option<u64>,
+    pub browser: Option<String>,
+    pub gpu: Option<String>,
+    /// A short tag for the OS, like `win11`, used in result file names.
+    pub os_tag: Option<String>,
+    /// Which configurations of the matrix were chosen. Missing in runs from before designs
+    /// could be chosen, which were full factorials.
+    pub design: Option<Design>,
+    /// The [`SCHEMA_VERSION`] of the results file. Missing in runs from before schemas were
+    /// versioned, whose version is told from the columns they have.
+    pub schema_version: Option<u32>,
+}
+
+impl RunMetadata {
+    /// Collects metadata about the current environment. Expects to be run from the workspace
+    /// directory.
+    pub fn collect(browser_path: &Path, design: Design) -> RunMetadata {
+        let started_unix_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .ok();
+
+        let (browser, gpu) = match browser_info(browser_path) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("Couldn't read browser version: {e:#}");
+                (None, None)
+            }
+        };
+
+        RunMetadata {
+            started_unix_secs,
+            rustc: version_of("rustc"),
+            cargo: version_of("cargo"),
+            wasm_bindgen: version_of("wasm-bindgen"),
+            wasm_opt: version_of(WASM_OPT_COMMAND),
+            bevy: lock_version("bevy").ok().flatten(),
+            git_commit: command_output("git", &["rev-parse", "HEAD"]),
+            git_dirty: git_dirty(),
+            os: os_description(),
+            cpu: cpu_model(),
+            cores: std::thread::available_parallelism().ok().map(|n| n.get()),
+            memory_bytes: total_memory(),
+            browser,
+            gpu,
+            os_tag: os_tag(),
+            design: Some(design),
+            schema_version: Some(SCHEMA_VERSION),
+        }
+    }
+
+    /// A file name stem for results from this run, like `42674c1_win11_i713700KF_4080super`.
+    pub fn result_name(&self) -> String {
+        let mut commit = self
+            .git_commit
+            .as_deref()
+            .map(|commit| commit.chars().take(7).collect::<String>())
+            .unwrap_or_else(|| "unknown".to_string());
+        if self.git_dirty == Some(true) {
+            commit.push_str("-dirty");
+        }
+
+        let mut parts = vec![commit];
+        parts.extend(self.os_tag.clone());
+        parts.extend(self.cpu.as_deref().map(short_cpu));
+        parts.extend(self.gpu.as_deref().map(short_gpu));
+
+        parts
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+
+    pub fn load(path: &Path) -> Result<RunMetadata> {
+        let json =
+            std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Writing {}", path.display()))
+    }
+
+    /// Names of the fields that differ from `other`, ignoring the start time.
+    pub fn changes_from(&self, other: &RunMetadata) -> Vec<String> {
+        let (Ok(Value::Object(this)), Ok(Value::Object(other))) =
+            (serde_json::to_value(self), serde_json::to_value(other))
+        else {
+            return vec![];
+        };
+
+        this.iter()
+            .filter(|(field, value)| {
+                *field != "started_unix_secs" && other.get(field.as_str()) != Some(value)
+            })
+            .map(|(field, _)| field.clone())
+            .collect()
+    }
+}
+
+/// The metadata file belonging to a results CSV, like `out.meta.json` for `out.csv`.
+pub fn metadata_path(csv_path: &Path) -> PathBuf {
+    csv_path.with_extension("meta.json")
+}
+
+/// Looks up the version of a package in the workspace's `Cargo.lock`. If several versions are
+/// locked, the highest is returned.
+pub fn lock_version(package: &str) -> Result<Option<String>> {
+    #[derive(Deserialize)]
+    struct Lockfile {
+        #[serde(default)]
+        package: Vec<LockedPackage>,
+    }
+    #[derive(Deserialize)]
+    struct LockedPackage {
+        name: String,
+        version: String,
+    }
+
+    let lock = std::fs::read_to_string("Cargo.lock").context("Reading Cargo.lock")?;
+    let lock: Lockfile = toml::from_str(&lock).context("Parsing Cargo.lock")?;
+
+    Ok(lock
+        .package
+        .into_iter()
+        .filter(|locked| locked.name == package)
+        .map(|locked| locked.version)
+        .max_by_key(|version| version_key(version)))
+}
+
+/// Sorts versions like `0.9.1` before `0.14.0`. Parts that aren't numbers count as 0.
+pub fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-', '+'])
+        .map(|part| part.parse::<u64>().unwrap_or(0))
+        .collect()
+}
+
+/// Runs `program --version` and returns the first line of its output.
+pub fn version_of(program: &str) -> Option<String> {
+    command_output(program, &["--version"])
+        .and_then(|output| output.lines().next().map(str::to_string))
+}
+
+/// Runs a command and returns its trimmed stdout, if it succeeded and printed anything.
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some(stdout).filter(|stdout| !stdout.is_empty())
+}
+
+fn git_dirty() -> Option<bool> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| !output.stdout.trim_ascii().is_empty())
+}
+
+fn browser_info(browser_path: &Path) -> Result<(Option<String>, Option<String>)> {
+    let mut browser = Browser::launch(browser_path)?;
+    let version = browser.version()?;
+    let gpu = browser.gpu()?;
+    browser.close()?;
+
+    Ok((Some(version), gpu))
+}
+
+/// Reads `KEY=value` or `KEY: value` style lines, like those in `/etc/os-release` and
+/// `/proc/cpuinfo`.
+#[cfg(target_os = "linux")]
+fn read_field(path: &str, key: &str, separator: char) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+
+    contents.lines().find_map(|line| {
+        let (k, v) = line.split_once(separator)?;
+        (k.trim() == key).then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn os_description() -> Option<String> {
+    read_field("/etc/os-release", "PRETTY_NAME", '=').or_else(|| Some("Linux".to_string()))
+}
+
+#[cfg(target_os = "linux")]
+fn os_tag() -> Option<String> {
+    let id = read_field("/etc/os-release", "ID", '=').unwrap_or_else(|| "linux".to_string());
+    let version = read_field("/etc/os-release", "VERSION_ID", '=').unwrap_or_default();
+
+    Some(format!("{id}{version}"))
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_model() -> Option<String> {
+    read_field("/proc/cpuinfo", "model name", ':')
+}
+
+#[cfg(target_os = "linux")]
+fn total_memory() -> Option<u64> {
+    let kb = read_field("/proc/meminfo", "MemTotal", ':')?;
+    let kb = kb.trim_end_matches("kB").trim().parse::<u64>().ok()?;
+
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "macos")]
+fn os_description() -> Option<String> {
+    command_output("sw_vers", &["-productVersion"]).map(|version| format!("macOS {version}"))
+}
+
+#[cfg(target_os = "macos")]
+fn os_tag() -> Option<String> {
+    let version = command_output("sw_vers", &["-productVersion"])?;
+    let major = version.split('.').next()?;
+
+    Some(format!("macos{major}"))
+}
+
+#[cfg(target_os = "macos")]
+fn cpu_model() -> Option<String> {
+    command_output("sysctl", &["-n", "machdep.cpu.brand_string"])
+}
+
+#[cfg(target_os = "macos")]
+fn total_memory() -> Option<u64> {
+    command_output("sysctl", &["-n", "hw.memsize"])?
+        .parse()
+        .ok()
+}
+
+/// The Windows build number, from output like `Microsoft Windows [Version 10.0.22631.3880]`.
+#[cfg(target_os = "windows")]
+fn windows_version() -> Option<String> {
+    let ver = command_output("cmd", &["/c", "ver"])?;
+    let (_, version) = ver.split_once("Version ")?;
+
+    Some(version.trim_end_matches(']').to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn os_description() -> Option<String> {
+    windows_version().map(|version| format!("Windows {version}"))
+}
+
+#[cfg(target_os = "windows")]
+fn os_tag() -> Option<String> {
+    let version = windows_version()?;
+    let build = version.split('.').nth(2)?.parse::<u32>().ok()?;
+
+    // Windows 11 still reports itself as version 10, but with builds from 22000 on.
+    Some(if build >= 22000 { "win11" } else { "win10" }.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn cpu_model() -> Option<String> {
+    let output = command_output(
+        "reg",
+        &[
+            "query",
+            "HKLM\\HARDWARE\\DESCRIPTION\\System\\CentralProcessor\\0",
+            "/v",
+            "ProcessorNameString",
+        ],
+    )?;
+    let (_, name) = output.split_once("REG_SZ")?;
+
+    Some(name.trim().to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn total_memory() -> Option<u64> {
+    command_output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "(Get-CimInstance Win32_ComputerSystem).TotalPhysicalMemory",
+        ],
+    )?
+    .parse()
+    .ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn os_description() -> Option<String> {
+    Some(std::env::consts::OS.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn os_tag() -> Option<String> {
+    Some(std::env::consts::OS.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn cpu_model() -> Option<String> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn total_memory() -> Option<u64> {
+    None
+}
+
+/// Shortens a CPU model, like `Intel(R) Core(TM) i7-13700KF` to `i713700KF`.
+fn short_cpu(cpu: &str) -> String {
+    const NOISE: &[&str] = &["intel", "amd", "apple", "core", "processor", "cpu"];
+
+    shorten(cpu, NOISE)
+}
+
+/// Shortens a GPU name, like `NVIDIA GeForce RTX 4080 SUPER` to `4080super`.
+fn short_gpu(gpu: &str) -> String {
+    const NOISE: &[&str] = &[
+        "nvidia", "geforce", "rtx", "gtx", "amd", "radeon", "intel", "apple", "graphics",
+    ];
+
+    shorten(gpu, NOISE).to_lowercase()
+}
+
+/// Drops trademark signs, clock speeds, core counts and `noise` words, then squashes the rest
+/// into a single alphanumeric word.
+fn shorten(name: &str, noise: &[&str]) -> String {
+    let name = name
+        .replace("(R)", "")
+        .replace("(TM)", "")
+        .replace("(tm)", "");
+    // Clock speeds come last, like `@ 3.40GHz`.
+    let name = name.split('@').next().unwrap_or_default();
+
+    name.split_whitespace()
+        .filter(|word| {
+            let word = word.to_lowercase();
+            !noise.contains(&word.as_str()) && !word.ends_with("-core") && !word.ends_with("ghz")
+        })
+        .flat_map(|word| word.chars().filter(|c| c.is_ascii_alphanumeric()))
+        .collect()
+}
@@ -0,0 +1,73 @@
+//! Rewrites results files from older versions of the runner in the current schema.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+use crate::cli::MigrateArgs;
+use crate::metadata::{self, RunMetadata};
+use crate::results::{self, CSV_HEADER, SAMPLES_HEADER, SCHEMA_VERSION};
+
+/// Rewrites each results file from an older schema version in the current one, along with its
+/// samples file, filling in defaults for values its version didn't record and leaving the
+/// columns it never measured, like `contended`, empty. Records the version in its metadata
+/// file, which is created if there isn't one, with everything else about the run unknown.
+pub fn migrate(args: &MigrateArgs) -> Result<()> {
+    for path in &args.inputs {
+        let version = results::schema_version(path)?;
+        let metadata_path = metadata::metadata_path(path);
+        let mut metadata = if metadata_path.exists() {
+            RunMetadata::load(&metadata_path)?
+        } else {
+            RunMetadata::default()
+        };
+
+        if version == SCHEMA_VERSION {
+            if metadata.schema_version.is_some() {
+                println!(
+                    "{} is already at schema version {SCHEMA_VERSION}.",
+                    path.display()
+                );
+            } else {
+                metadata.schema_version = Some(SCHEMA_VERSION);
+                metadata.write(&metadata_path)?;
+                println!(
+                    "Recorded schema version {SCHEMA_VERSION} for {}.",
+                    path.display()
+                );
+            }
+            continue;
+        }
+
+        // Read everything before truncating the file.
+        let records = results::read_records(path)?;
+
+        let mut csv = results::create(path, CSV_HEADER)?;
+        for record in &records {
+            writeln!(csv, "{}", record.to_csv_row())
+                .with_context(|| format!("Writing {}", path.display()))?;
+        }
+
+        let samples_path = results::samples_path(path);
+        if samples_path.exists() {
+            let samples = results::read_samples(&samples_path)?;
+
+            let mut samples_csv = results::create(&samples_path, SAMPLES_HEADER)?;
+            for sample in &samples {
+                writeln!(samples_csv, "{}", sample)
+                    .with_context(|| format!("Writing {}", samples_path.display()))?;
+            }
+        }
+
+        metadata.schema_version = Some(SCHEMA_VERSION);
+        metadata.write(&metadata_path)?;
+
+        println!(
+            "Migrated {} rows of {} from schema version {version} to {SCHEMA_VERSION}.",
+            records.len(),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
-use strum_macros::EnumIter;
+use std::path::{Path, PathBuf};
 
-#[derive(EnumIter, Debug, Clone)]
+use serde::Deserialize;
+use strum_macros::{EnumIter, EnumString};
+
+use crate::PROFILE;
+
+#[derive(EnumIter, EnumString, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OptLevel {
     S,
     Z,
@@ -16,7 +21,7 @@ impl OptLevel {
     }
 }
 
-#[derive(EnumIter, Debug, Clone)]
+#[derive(EnumIter, EnumString, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Lto {
     Off,
     Thin,
@@ -32,7 +37,7 @@ impl Lto {
     }
 }
 
-#[derive(EnumIter, Debug, Clone)]
+#[derive(EnumIter, EnumString, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CodegenUnits {
     One,
     Default,
@@ -46,7 +51,7 @@ impl CodegenUnits {
     }
 }
 
-#[derive(EnumIter, Debug, Clone)]
+#[derive(EnumIter, EnumString, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Strip {
     None,
     DebugInfo,
@@ -60,7 +65,7 @@ impl Strip {
     }
 }
 
-#[derive(EnumIter, Debug)]
+#[derive(EnumIter, EnumString, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum WasmOpt {
     None,
     S,
@@ -89,7 +94,7 @@ impl WasmOpt {
     }
 }
 
-#[derive(EnumIter, Debug)]
+#[derive(EnumIter, EnumString, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Panic {
     Unwind,
     Abort,
@@ -102,3 +107,54 @@ impl Panic {
         }
     }
 }
+
+/// The cargo profile settings that require a separate build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CargoOptions {
+    pub opt_level: OptLevel,
+    pub lto: Lto,
+    pub codegen_units: CodegenUnits,
+    pub strip: Strip,
+    pub panic: Panic,
+}
+impl CargoOptions {
+    pub fn options_toml(&self) -> String {
+        [
+            self.opt_level.option(),
+            self.lto.option(),
+            self.codegen_units.option(),
+            self.strip.option(),
+            self.panic.option(),
+        ]
+        .join("\n")
+    }
+
+    /// The contents of a cargo config file defining the bench profile with these options.
+    pub fn config_toml(&self) -> String {
+        format!(
+            "[profile.{}]\ninherits = \"release\"\n{}",
+            PROFILE,
+            self.options_toml()
+        )
+    }
+
+    /// A short name identifying this combination of options, e.g. `S-Fat-One-None-Unwind`.
+    pub fn name(&self) -> String {
+        format!(
+            "{:?}-{:?}-{:?}-{:?}-{:?}",
+            self.opt_level, self.lto, self.codegen_units, self.strip, self.panic
+        )
+    }
+
+    /// A target directory dedicated to this combination of options.
+    pub fn target_dir(&self) -> PathBuf {
+        Path::new("target/bench").join(self.name())
+    }
+}
+
+/// A single benchmarked configuration: a cargo build followed by a wasm-opt pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub cargo: CargoOptions,
+    pub wasm_opt: WasmOpt,
+}
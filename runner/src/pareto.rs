@@ -0,0 +1,29 @@
+//! Pareto fronts over several objectives, all of which are minimized.
+
+/// Whether `a` is at least as good as `b` in every objective and better in at least one.
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a <= b) && a.iter().zip(b).any(|(a, b)| a < b)
+}
+
+/// Sorts points into non-dominated layers and returns the layer of each point. Layer 0 is the
+/// Pareto front, layer 1 is the front once layer 0 is removed, and so on.
+pub fn layers(points: &[Vec<f64>]) -> Vec<usize> {
+    let mut layers = vec![usize::MAX; points.len()];
+    let mut layer = 0;
+
+    while layers.contains(&usize::MAX) {
+        let remaining = (0..points.len())
+            .filter(|&i| layers[i] == usize::MAX)
+            .collect::<Vec<_>>();
+
+        for &i in &remaining {
+            if !remaining.iter().any(|&j| dominates(&points[j], &points[i])) {
+                layers[i] = layer;
+            }
+        }
+
+        layer += 1;
+    }
+
+    layers
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Condvar, Mutex, MutexGuard};
+
+use anyhow::Result;
+
+use crate::build::{build, Artifact, BuildOptions};
+use crate::options::*;
+
+/// Tracks work that is running concurrently, so that timings which overlapped with other
+/// work can be flagged as contended.
+///
+/// Also keeps measurements isolated: a measurement waits for running work to finish, and no new
+/// work starts until it's done.
+#[derive(Default)]
+pub struct Activity {
+    inner: Mutex<ActivityInner>,
+    /// Notified whenever a task ends.
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct ActivityInner {
+    next_id: usize,
+    active: HashSet<usize>,
+    contended: HashSet<usize>,
+    /// The task of the measurement that is in progress or waiting to start, if any.
+    measurement: Option<usize>,
+}
+
+impl Activity {
+    /// Registers the start of a piece of work, waiting for any measurement in progress to
+    /// finish first. It stays active until the returned [`Task`] is finished or dropped.
+    pub fn start(&self) -> Task<'_> {
+        let inner = self.inner.lock().unwrap();
+        let inner = self
+            .changed
+            .wait_while(inner, |inner| inner.measurement.is_some())
+            .unwrap();
+
+        Self::register(inner, self)
+    }
+
+    /// Registers the start of a measurement, waiting for running work to finish first. No new
+    /// work starts until the returned [`Task`] is finished or dropped.
+    pub fn start_measurement(&self) -> Task<'_> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.measurement = Some(inner.next_id);
+
+        let inner = self
+            .changed
+            .wait_while(inner, |inner| !inner.active.is_empty())
+            .unwrap();
+
+        Self::register(inner, self)
+    }
+
+    fn register<'a>(mut inner: MutexGuard<ActivityInner>, activity: &'a Activity) -> Task<'a> {
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        if !inner.active.is_empty() {
+            let active = inner.active.iter().copied().collect::<Vec<_>>();
+            inner.contended.extend(active);
+            inner.contended.insert(id);
+        }
+        inner.active.insert(id);
+
+        Task { activity, id }
+    }
+}
+
+pub struct Task<'a> {
+    activity: &'a Activity,
+    id: usize,
+}
+
+impl Task<'_> {
+    /// Ends the task, returning whether any other work ran while it was active.
+    pub fn finish(self) -> bool {
+        self.activity
+            .inner
+            .lock()
+            .unwrap()
+            .contended
+            .contains(&self.id)
+    }
+}
+
+impl Drop for Task<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.activity.inner.lock().unwrap();
+        inner.active.remove(&self.id);
+        inner.contended.remove(&self.id);
+
+        if inner.measurement == Some(self.id) {
+            inner.measurement = None;
+        }
+        self.activity.changed.notify_all();
+    }
+}
+
+/// Builds every configuration and passes the resulting artifacts to `measure`, in order.
+///
+/// With `jobs == 0`, each build runs to completion before its artifacts are measured. Otherwise
+/// up to `jobs` builds run on worker threads between measurements of earlier artifacts, and
+/// `measure` is still only ever called from the current thread, one artifact at a time. Workers
+/// don't start builds or wasm-opt passes while `activity` has a measurement in progress, and
+/// no more than `jobs` builds are running or waiting to be measured at once.
+pub fn run<F>(
+    builds: Vec<(CargoOptions, Vec<WasmOpt>)>,
+    jobs: usize,
+    options: &BuildOptions,
+    activity: &Activity,
+    mut measure: F,
+) -> Result<()>
+where
+    F: FnMut(Artifact) -> Result<()>,
+{
+    if jobs == 0 {
+        for (cargo, wasm_opts) in builds {
+            for artifact in build(cargo, &wasm_opts, options, activity)? {
+                measure(artifact)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let num_builds = builds.len();
+    let queue = Mutex::new(builds.into_iter().enumerate().collect::<VecDeque<_>>());
+    let cancelled = AtomicBool::new(false);
+    // Builds that are running or waiting to be measured. Builds are taken from the queue in
+    // order, so the next one to be measured always has a slot.
+    let slots = Mutex::new(0);
+    let slot_freed = Condvar::new();
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(num_builds) {
+            let sender = sender.clone();
+            let queue = &queue;
+            let cancelled = &cancelled;
+            let slots = &slots;
+            let slot_freed = &slot_freed;
+
+            scope.spawn(move || loop {
+                {
+                    let mut slots = slot_freed
+                        .wait_while(slots.lock().unwrap(), |slots| {
+                            *slots == jobs && !cancelled.load(Ordering::SeqCst)
+                        })
+                        .unwrap();
+                    if cancelled.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    *slots += 1;
+                }
+
+                let Some((i, (cargo, wasm_opts))) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+
+                let result = build(cargo, &wasm_opts, options, activity);
+
+                if sender.send((i, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let result = (|| {
+            let mut pending = BTreeMap::new();
+
+            for i in 0..num_builds {
+                let artifacts = loop {
+                    if let Some(artifacts) = pending.remove(&i) {
+                        break artifacts;
+                    }
+
+                    let (j, artifacts) = receiver.recv()?;
+                    pending.insert(j, artifacts);
+                };
+
+                for artifact in artifacts? {
+                    measure(artifact)?;
+                }
+
+                *slots.lock().unwrap() -= 1;
+                slot_freed.notify_one();
+            }
+
+            Ok(())
+        })();
+
+        if result.is_err() {
+            println!("Waiting for running builds to finish.");
+            {
+                // Hold the lock so no worker misses the notification.
+                let _slots = slots.lock().unwrap();
+                cancelled.store(true, Ordering::SeqCst);
+            }
+            slot_freed.notify_all();
+            // Workers that finish a build now get an error from `send` instead of waiting.
+            drop(receiver);
+        }
+
+        result
+    })
+}
@@ -0,0 +1,215 @@
+//! Describes what a run would do without doing it.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::options::*;
+use crate::results;
+use crate::{CSV_PATH, RESULTS_DIR};
+
+/// Rough length of one browser session: loading, spawning birds until the bench is full, then
+/// the measurement window itself.
+const SESSION_ESTIMATE: Duration = Duration::from_secs(27);
+
+/// Prints every configuration that would be built and measured, the cargo config for each
+/// build, and an estimate of how long it would all take.
+pub fn print_plan(configs: &[Config], repetitions: usize) -> Result<()> {
+    let history = History::load()?;
+
+    let builds = configs
+        .iter()
+        .chunk_by(|config| config.cargo)
+        .into_iter()
+        .map(|(cargo, group)| (cargo, group.map(|c| c.wasm_opt).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+
+    let mut build_total = Duration::ZERO;
+    let mut wasm_opt_total = Duration::ZERO;
+    let mut unknown = 0;
+
+    for (i, (cargo, wasm_opts)) in builds.iter().enumerate() {
+        let build_time = history.build_time(cargo);
+
+        println!(
+            "Build {}/{}: {} ({})",
+            i + 1,
+            builds.len(),
+            cargo.name(),
+            estimate(build_time)
+        );
+        for line in cargo.config_toml().lines().filter(|line| !line.is_empty()) {
+            println!("    {line}");
+        }
+
+        build_total += build_time.unwrap_or_default();
+        unknown += build_time.is_none() as usize;
+
+        for wasm_opt in wasm_opts {
+            let config = Config {
+                cargo: *cargo,
+                wasm_opt: *wasm_opt,
+            };
+            let wasm_opt_time = if wasm_opt.enabled() {
+                history.wasm_opt_time(&config)
+            } else {
+                Some(Duration::ZERO)
+            };
+
+            let mut line = format!(
+                "  WasmOpt::{:?}, {} repetition{}",
+                wasm_opt,
+                repetitions,
+                if repetitions == 1 { "" } else { "s" }
+            );
+            if wasm_opt.enabled() {
+                line.push_str(&format!(" (wasm-opt {})", estimate(wasm_opt_time)));
+            }
+            println!("{line}");
+
+            wasm_opt_total += wasm_opt_time.unwrap_or_default();
+            unknown += wasm_opt_time.is_none() as usize;
+        }
+        println!();
+    }
+
+    let sessions = configs.len() * repetitions;
+    let measure_total = SESSION_ESTIMATE * sessions as u32;
+
+    println!(
+        "{} configurations in {} builds, {} browser sessions.",
+        configs.len(),
+        builds.len(),
+        sessions
+    );
+
+    if history.is_empty() {
+        println!(
+            "No earlier results in {} or {} to estimate build times from.",
+            CSV_PATH, RESULTS_DIR
+        );
+        println!(
+            "Measuring alone takes about {}.",
+            format_duration(measure_total)
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Estimated duration: {} (builds {}, wasm-opt {}, measuring {}).",
+        format_duration(build_total + wasm_opt_total + measure_total),
+        format_duration(build_total),
+        format_duration(wasm_opt_total),
+        format_duration(measure_total)
+    );
+    if unknown > 0 {
+        println!("{unknown} steps have no earlier timings and aren't included.");
+    }
+    println!("This assumes clean builds run one after another, as without --cache or --jobs.");
+
+    Ok(())
+}
+
+/// Build timings from earlier results files, averaged per configuration.
+#[derive(Default)]
+struct History {
+    build_times: HashMap<CargoOptions, Vec<f64>>,
+    wasm_opt_times: HashMap<Config, Vec<f64>>,
+}
+
+impl History {
+    fn load() -> Result<History> {
+        let mut paths = vec![PathBuf::from(CSV_PATH)];
+        if let Ok(entries) = std::fs::read_dir(RESULTS_DIR) {
+            for entry in entries {
+                paths.push(entry?.path());
+            }
+        }
+
+        let mut history = History::default();
+
+        for path in paths {
+            if path.extension().is_none_or(|extension| extension != "csv")
+                || results::is_samples_path(&path)
+                || !path.exists()
+            {
+                continue;
+            }
+
+            let rows = match results::read_results(&path) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    println!("Skipping {} in the estimate: {e:#}", path.display());
+                    continue;
+                }
+            };
+
+            for row in rows {
+                let (Some(build_time), Some(wasm_opt_time)) =
+                    (row.value("build_time"), row.value("wasm_opt_time"))
+                else {
+                    continue;
+                };
+
+                history
+                    .build_times
+                    .entry(row.config.cargo)
+                    .or_default()
+                    .push(build_time);
+                history
+                    .wasm_opt_times
+                    .entry(row.config)
+                    .or_default()
+                    .push(wasm_opt_time);
+            }
+        }
+
+        Ok(history)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.build_times.is_empty()
+    }
+
+    fn build_time(&self, cargo: &CargoOptions) -> Option<Duration> {
+        mean(self.build_times.get(cargo)?)
+    }
+
+    fn wasm_opt_time(&self, config: &Config) -> Option<Duration> {
+        mean(self.wasm_opt_times.get(config)?)
+    }
+}
+
+fn mean(secs: &[f64]) -> Option<Duration> {
+    if secs.is_empty() {
+        return None;
+    }
+
+    Some(Duration::from_secs_f64(
+        secs.iter().sum::<f64>() / secs.len() as f64,
+    ))
+}
+
+fn estimate(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("~{}", format_duration(duration)),
+        None => "no earlier timing".to_string(),
+    }
+}
+
+/// Formats a duration like `2h 5m`, `3m 20s` or `45s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
@@ -0,0 +1,175 @@
+//! Minimal SVG line charts.
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 420.0;
+const LEFT: f64 = 90.0;
+const RIGHT: f64 = 160.0;
+const TOP: f64 = 50.0;
+const BOTTOM: f64 = 60.0;
+
+/// Colors for each series, from seaborn's "deep" palette like the plots in `analysis`.
+const PALETTE: [&str; 8] = [
+    "#4c72b0", "#dd8452", "#55a868", "#c44e52", "#8172b3", "#937860", "#da8bc3", "#8c8c8c",
+];
+
+/// A line of values, one for each category on the x axis. Missing values break the line.
+pub struct Series {
+    pub name: String,
+    pub values: Vec<Option<f64>>,
+}
+
+/// Draws series of values over categories, with evenly spaced categories on the x axis and a
+/// legend of series on the right. `format` formats y axis labels.
+pub fn line_chart(
+    title: &str,
+    x_label: &str,
+    categories: &[String],
+    legend_title: &str,
+    series: &[Series],
+    format: impl Fn(f64) -> String,
+) -> String {
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let plot_height = HEIGHT - TOP - BOTTOM;
+
+    let values = series
+        .iter()
+        .flat_map(|series| series.values.iter().flatten().copied())
+        .collect::<Vec<_>>();
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        });
+    let (min, max) = if values.is_empty() {
+        (0.0, 1.0)
+    } else if min == max {
+        (min - 0.5, max + 0.5)
+    } else {
+        let padding = (max - min) * 0.05;
+        (min - padding, max + padding)
+    };
+
+    let x = |i: usize| LEFT + plot_width * (i as f64 + 0.5) / categories.len() as f64;
+    let y = |value: f64| TOP + plot_height * (max - value) / (max - min);
+
+    // One element per line.
+    let mut svg = vec![];
+    svg.push(format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" font-family="sans-serif" font-size="12">"#
+    ));
+    svg.push(format!(
+        r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#
+    ));
+    svg.push(format!(
+        r#"<text x="{}" y="25" text-anchor="middle" font-size="15">{}</text>"#,
+        LEFT + plot_width / 2.0,
+        escape(title)
+    ));
+
+    // Axes and grid
+
+    for tick in ticks(min, max) {
+        svg.push(format!(
+            r##"<line x1="{LEFT}" x2="{}" y1="{y:.1}" y2="{y:.1}" stroke="#e5e5e5"/><text x="{}" y="{:.1}" text-anchor="end">{}</text>"##,
+            LEFT + plot_width,
+            LEFT - 6.0,
+            y(tick) + 4.0,
+            escape(&format(tick)),
+            y = y(tick),
+        ));
+    }
+    for (i, category) in categories.iter().enumerate() {
+        svg.push(format!(
+            r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+            x(i),
+            TOP + plot_height + 18.0,
+            escape(category)
+        ));
+    }
+    svg.push(format!(
+        r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+        LEFT + plot_width / 2.0,
+        HEIGHT - 15.0,
+        escape(x_label)
+    ));
+    svg.push(format!(
+        r#"<rect x="{LEFT}" y="{TOP}" width="{plot_width}" height="{plot_height}" fill="none" stroke="black"/>"#
+    ));
+
+    // Series
+
+    for (series, color) in series.iter().zip(PALETTE.iter().cycle()) {
+        let mut path = String::new();
+        let mut pen_down = false;
+
+        for (i, value) in series.values.iter().enumerate() {
+            let Some(value) = value else {
+                pen_down = false;
+                continue;
+            };
+
+            path.push_str(&format!(
+                "{}{:.1},{:.1} ",
+                if pen_down { "L" } else { "M" },
+                x(i),
+                y(*value)
+            ));
+            pen_down = true;
+
+            svg.push(format!(
+                r#"<circle cx="{:.1}" cy="{:.1}" r="4" fill="{color}"/>"#,
+                x(i),
+                y(*value)
+            ));
+        }
+
+        svg.push(format!(
+            r#"<path d="{}" fill="none" stroke="{color}" stroke-width="2"/>"#,
+            path.trim_end()
+        ));
+    }
+
+    // Legend
+
+    let legend_x = LEFT + plot_width + 20.0;
+    svg.push(format!(
+        r#"<text x="{legend_x}" y="{}">{}</text>"#,
+        TOP + 10.0,
+        escape(legend_title)
+    ));
+    for (i, (series, color)) in series.iter().zip(PALETTE.iter().cycle()).enumerate() {
+        let legend_y = TOP + 30.0 + 20.0 * i as f64;
+        svg.push(format!(
+            r#"<line x1="{legend_x}" x2="{}" y1="{legend_y}" y2="{legend_y}" stroke="{color}" stroke-width="2"/><text x="{}" y="{}">{}</text>"#,
+            legend_x + 20.0,
+            legend_x + 26.0,
+            legend_y + 4.0,
+            escape(&series.name)
+        ));
+    }
+
+    svg.push("</svg>\n".to_string());
+    svg.join("\n")
+}
+
+/// Round numbers spanning `min..max`, about five of them.
+fn ticks(min: f64, max: f64) -> Vec<f64> {
+    let rough = (max - min) / 5.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,174 @@
+//! Ordinary least squares regression.
+
+use anyhow::Result;
+
+use crate::stats::{f_p_value, t_p_value, t_quantile};
+
+/// Columns whose pivot is smaller than this, relative to the largest diagonal entry of `XᵀX`,
+/// are treated as linear combinations of earlier columns.
+const SINGULAR_TOLERANCE: f64 = 1e-10;
+
+/// A fitted linear model.
+#[derive(Debug)]
+pub struct Fit {
+    pub coefficients: Vec<Coefficient>,
+    pub observations: usize,
+    /// Residual degrees of freedom.
+    pub df: usize,
+    /// Residual sum of squares.
+    pub rss: f64,
+    pub r_squared: f64,
+    pub adj_r_squared: f64,
+}
+
+#[derive(Debug)]
+pub struct Coefficient {
+    pub term: String,
+    pub estimate: f64,
+    pub std_error: f64,
+    pub t: f64,
+    pub p_value: f64,
+    /// Bounds of the 95% confidence interval.
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+impl Fit {
+    /// Residual standard error.
+    pub fn sigma(&self) -> f64 {
+        (self.rss / self.df as f64).sqrt()
+    }
+}
+
+/// The result of testing whether the terms a full model adds to a reduced one explain more
+/// than chance would.
+#[derive(Debug)]
+pub struct FTest {
+    /// Number of coefficients the full model adds.
+    pub df: usize,
+    pub f: f64,
+    pub p_value: f64,
+}
+
+/// Compares a model with the same model fitted without some of its terms on the same data.
+pub fn f_test(reduced: &Fit, full: &Fit) -> FTest {
+    let df = reduced.df - full.df;
+    let f = ((reduced.rss - full.rss) / df as f64) / (full.rss / full.df as f64);
+
+    FTest {
+        df,
+        f,
+        p_value: f_p_value(f, df as f64, full.df as f64),
+    }
+}
+
+/// Fits `y = Xβ + ε` by least squares. `x` holds one row of predictors per observation, and
+/// `terms` names each predictor.
+///
+/// Fails if a predictor is a linear combination of the others, naming that predictor, or if
+/// there are no residual degrees of freedom left.
+pub fn ols(terms: &[String], x: &[Vec<f64>], y: &[f64]) -> Result<Fit> {
+    let n = y.len();
+    let p = terms.len();
+
+    if n <= p {
+        anyhow::bail!("{n} observations aren't enough to fit {p} coefficients");
+    }
+
+    // Invert XᵀX by Gauss-Jordan elimination.
+
+    let mut xtx = vec![vec![0.0; p]; p];
+    let mut xty = vec![0.0; p];
+    for (row, y) in x.iter().zip(y) {
+        for i in 0..p {
+            xty[i] += row[i] * y;
+            for j in 0..p {
+                xtx[i][j] += row[i] * row[j];
+            }
+        }
+    }
+
+    let scale = (0..p).map(|i| xtx[i][i]).fold(0.0, f64::max);
+    let mut inverse = (0..p)
+        .map(|i| (0..p).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect::<Vec<Vec<f64>>>();
+
+    for column in 0..p {
+        // XᵀX is symmetric positive semi-definite, so the diagonal is a safe pivot. A tiny one
+        // means the column adds nothing the earlier columns don't already explain.
+        let pivot = xtx[column][column];
+        if pivot.abs() <= SINGULAR_TOLERANCE * scale {
+            anyhow::bail!(
+                "{} can't be estimated, because it's a combination of the other terms in this data",
+                terms[column]
+            );
+        }
+
+        for j in 0..p {
+            xtx[column][j] /= pivot;
+            inverse[column][j] /= pivot;
+        }
+
+        for i in 0..p {
+            if i == column {
+                continue;
+            }
+            let factor = xtx[i][column];
+            for j in 0..p {
+                xtx[i][j] -= factor * xtx[column][j];
+                inverse[i][j] -= factor * inverse[column][j];
+            }
+        }
+    }
+
+    let estimates = (0..p)
+        .map(|i| (0..p).map(|j| inverse[i][j] * xty[j]).sum::<f64>())
+        .collect::<Vec<_>>();
+
+    // Residuals
+
+    let fitted = x
+        .iter()
+        .map(|row| row.iter().zip(&estimates).map(|(x, b)| x * b).sum::<f64>())
+        .collect::<Vec<_>>();
+    let rss = y
+        .iter()
+        .zip(&fitted)
+        .map(|(y, fitted)| (y - fitted).powi(2))
+        .sum::<f64>();
+    let mean = y.iter().sum::<f64>() / n as f64;
+    let tss = y.iter().map(|y| (y - mean).powi(2)).sum::<f64>();
+
+    let df = n - p;
+    let sigma_squared = rss / df as f64;
+    let t_critical = t_quantile(0.975, df as f64);
+
+    let coefficients = (0..p)
+        .map(|i| {
+            let estimate = estimates[i];
+            let std_error = (sigma_squared * inverse[i][i]).sqrt();
+            let t = estimate / std_error;
+
+            Coefficient {
+                term: terms[i].clone(),
+                estimate,
+                std_error,
+                t,
+                p_value: t_p_value(t, df as f64),
+                ci_low: estimate - t_critical * std_error,
+                ci_high: estimate + t_critical * std_error,
+            }
+        })
+        .collect();
+
+    let r_squared = 1.0 - rss / tss;
+
+    Ok(Fit {
+        coefficients,
+        observations: n,
+        df,
+        rss,
+        r_squared,
+        adj_r_squared: 1.0 - (1.0 - r_squared) * (n - 1) as f64 / df as f64,
+    })
+}
@@ -0,0 +1,422 @@
+//! Summarizes results files as Markdown tables.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use serde::Serialize;
+use size::Size;
+
+use crate::cli::ReportArgs;
+use crate::pareto;
+use crate::results::{self, Row};
+
+/// Prints every configuration in a results file, fastest first, followed by the Pareto front
+/// over the chosen objectives and the best configurations on it for each weighting.
+pub fn report(args: &ReportArgs) -> Result<()> {
+    let path = &args.input;
+    let mut rows = results::read_results(path)?;
+    if rows.is_empty() {
+        anyhow::bail!("{} has no results", path.display());
+    }
+
+    rows.sort_by(|a, b| {
+        let frame_time = |row: &Row| row.value("frame_time").unwrap_or(f64::INFINITY);
+        frame_time(a).total_cmp(&frame_time(b))
+    });
+
+    let table = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.config.cargo.name(),
+                format!("{:?}", row.config.wasm_opt),
+                format_ms(row.value("frame_time")),
+                format_size(row.value("size_gzipped")),
+                format_secs(row.value("build_time")),
+                format_secs(row.value("wasm_opt_time")),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    println!(
+        "{}",
+        markdown_table(
+            &[
+                "cargo",
+                "wasm_opt",
+                "frame_time",
+                "size_gzipped",
+                "build_time",
+                "wasm_opt_time"
+            ],
+            &table
+        )
+    );
+
+    let recommendations = recommend(&rows, &args.objectives, &args.weights, args.top)?;
+    println!("{}", recommendations.markdown(&args.objectives));
+
+    if let Some(json_path) = &args.json {
+        let json = serde_json::to_string_pretty(&recommendations)?;
+        std::fs::write(json_path, json)
+            .with_context(|| format!("Writing {}", json_path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Something to minimize: a column, or the sum of several, like `build_time+wasm_opt_time`.
+#[derive(Debug, Clone)]
+pub struct Objective {
+    columns: Vec<String>,
+}
+
+impl Objective {
+    /// The columns added together for this objective.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn parse(objective: &str) -> Result<Objective> {
+        let columns = objective
+            .split('+')
+            .map(|column| column.trim().to_string())
+            .collect::<Vec<_>>();
+        if columns.iter().any(|column| column.is_empty()) {
+            anyhow::bail!("Expected a column or columns joined by +, found {objective}");
+        }
+
+        Ok(Objective { columns })
+    }
+
+    pub fn name(&self) -> String {
+        self.columns.join("+")
+    }
+
+    pub fn value(&self, row: &Row) -> Option<f64> {
+        self.columns.iter().map(|column| row.value(column)).sum()
+    }
+
+    /// Formats a value in the unit of the first column.
+    pub fn format(&self, value: f64) -> String {
+        let column = self.columns[0].as_str();
+
+        if column.starts_with("size") {
+            format_size(Some(value))
+        } else if column == "build_time" || column == "wasm_opt_time" {
+            format_secs(Some(value))
+        } else if column.ends_with("_time") || column.starts_with("frame_") {
+            format_ms(Some(value))
+        } else {
+            format!("{value}")
+        }
+    }
+}
+
+/// Relative importance of each objective, in the same order.
+#[derive(Debug, Clone)]
+pub struct Weights(pub Vec<f64>);
+
+impl Weights {
+    pub fn parse(weights: &str) -> Result<Weights> {
+        let weights = weights
+            .split(',')
+            .map(|weight| {
+                weight
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|weight| *weight >= 0.0)
+                    .with_context(|| format!("Expected a non-negative weight, found {weight}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if weights.iter().all(|weight| *weight == 0.0) {
+            anyhow::bail!("At least one weight must be positive");
+        }
+
+        Ok(Weights(weights))
+    }
+}
+
+/// The Pareto front of a results file and rankings of it, as written by `--json`.
+#[derive(Serialize)]
+struct Recommendations {
+    objectives: Vec<String>,
+    /// Configurations that no other configuration beats in every objective, fastest first in
+    /// the first objective.
+    front: Vec<Entry>,
+    rankings: Vec<Ranking>,
+}
+
+#[derive(Serialize, Clone)]
+struct Entry {
+    opt_level: String,
+    wasm_opt: String,
+    lto: String,
+    codegen_units: String,
+    strip: String,
+    panic: String,
+    /// The value of each objective.
+    values: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize)]
+struct Ranking {
+    /// The weight of each objective, in the same order as `objectives`.
+    weights: Vec<f64>,
+    /// The best configurations on the front, best first.
+    ranked: Vec<Ranked>,
+}
+
+#[derive(Serialize)]
+struct Ranked {
+    /// The weighted mean of each objective, scaled to 0 for the best value on the front and 1
+    /// for the worst.
+    score: f64,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+/// Finds the Pareto front of `rows` over `objectives` and ranks it for each weighting, or for
+/// equal weights and each objective on its own if there are none.
+fn recommend(
+    rows: &[Row],
+    objectives: &[Objective],
+    weightings: &[Weights],
+    top: usize,
+) -> Result<Recommendations> {
+    let mut weightings = weightings.to_vec();
+    if weightings.is_empty() {
+        weightings.push(Weights(vec![1.0; objectives.len()]));
+        if objectives.len() > 1 {
+            for i in 0..objectives.len() {
+                let mut weights = vec![0.0; objectives.len()];
+                weights[i] = 1.0;
+                weightings.push(Weights(weights));
+            }
+        }
+    }
+
+    for weights in &weightings {
+        if weights.0.len() != objectives.len() {
+            anyhow::bail!(
+                "Expected {} weights, one for each of {}, found {}",
+                objectives.len(),
+                objectives.iter().map(Objective::name).join(", "),
+                weights.0.len()
+            );
+        }
+    }
+
+    // Find the front among rows with a value for every objective.
+
+    let (points, candidates): (Vec<Vec<f64>>, Vec<&Row>) = rows
+        .iter()
+        .filter_map(|row| {
+            let point = objectives
+                .iter()
+                .map(|objective| objective.value(row))
+                .collect::<Option<Vec<_>>>()?;
+            Some((point, row))
+        })
+        .unzip();
+
+    if candidates.is_empty() {
+        anyhow::bail!(
+            "No configurations have values for all of {}",
+            objectives.iter().map(Objective::name).join(", ")
+        );
+    }
+    if candidates.len() < rows.len() {
+        println!(
+            "{} configurations are missing values for some objectives and aren't considered.",
+            rows.len() - candidates.len()
+        );
+        println!();
+    }
+
+    let layers = pareto::layers(&points);
+    let mut front = (0..candidates.len())
+        .filter(|&i| layers[i] == 0)
+        .collect::<Vec<_>>();
+    front.sort_by(|a, b| points[*a][0].total_cmp(&points[*b][0]));
+
+    // Scale each objective to the range it covers on the front, so that weights don't depend
+    // on units.
+
+    let ranges = (0..objectives.len())
+        .map(|j| {
+            front
+                .iter()
+                .map(|&i| points[i][j])
+                .minmax()
+                .into_option()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let scaled = |i: usize, j: usize| {
+        let (min, max) = ranges[j];
+        if max > min {
+            (points[i][j] - min) / (max - min)
+        } else {
+            0.0
+        }
+    };
+
+    let front = front
+        .iter()
+        .map(|&i| {
+            let cargo = &candidates[i].config.cargo;
+            let entry = Entry {
+                opt_level: format!("{:?}", cargo.opt_level),
+                wasm_opt: format!("{:?}", candidates[i].config.wasm_opt),
+                lto: format!("{:?}", cargo.lto),
+                codegen_units: format!("{:?}", cargo.codegen_units),
+                strip: format!("{:?}", cargo.strip),
+                panic: format!("{:?}", cargo.panic),
+                values: objectives
+                    .iter()
+                    .map(Objective::name)
+                    .zip(points[i].iter().copied())
+                    .collect(),
+            };
+            (i, entry)
+        })
+        .collect::<Vec<_>>();
+
+    let rankings = weightings
+        .iter()
+        .map(|weights| {
+            let total = weights.0.iter().sum::<f64>();
+            let mut ranked = front
+                .iter()
+                .map(|(i, entry)| Ranked {
+                    score: (0..objectives.len())
+                        .map(|j| weights.0[j] * scaled(*i, j))
+                        .sum::<f64>()
+                        / total,
+                    entry: entry.clone(),
+                })
+                .collect::<Vec<_>>();
+            ranked.sort_by(|a, b| a.score.total_cmp(&b.score));
+            ranked.truncate(top);
+
+            Ranking {
+                weights: weights.0.clone(),
+                ranked,
+            }
+        })
+        .collect();
+
+    Ok(Recommendations {
+        objectives: objectives.iter().map(Objective::name).collect(),
+        front: front.into_iter().map(|(_, entry)| entry).collect(),
+        rankings,
+    })
+}
+
+impl Recommendations {
+    fn markdown(&self, objectives: &[Objective]) -> String {
+        let config_columns = |entry: &Entry| {
+            vec![
+                entry.opt_level.clone(),
+                entry.wasm_opt.clone(),
+                entry.lto.clone(),
+                entry.codegen_units.clone(),
+                entry.strip.clone(),
+                entry.panic.clone(),
+            ]
+        };
+        let objective_columns = |entry: &Entry| {
+            objectives
+                .iter()
+                .map(|objective| objective.format(entry.values[&objective.name()]))
+                .collect::<Vec<_>>()
+        };
+
+        let mut header = vec![
+            "opt_level",
+            "wasm_opt",
+            "lto",
+            "codegen_units",
+            "strip",
+            "panic",
+        ];
+        header.extend(self.objectives.iter().map(String::as_str));
+
+        let mut markdown = format!(
+            "## Pareto front\n\n{} configurations aren't beaten in every one of {} by any other.\n\n",
+            self.front.len(),
+            self.objectives.join(", ")
+        );
+        markdown.push_str(&markdown_table(
+            &header,
+            &self
+                .front
+                .iter()
+                .map(|entry| [config_columns(entry), objective_columns(entry)].concat())
+                .collect::<Vec<_>>(),
+        ));
+
+        let mut ranked_header = vec!["rank"];
+        ranked_header.extend(&header);
+        ranked_header.push("score");
+
+        for ranking in &self.rankings {
+            markdown.push_str(&format!(
+                "\n## Recommendations for {}\n\n",
+                self.objectives
+                    .iter()
+                    .zip(&ranking.weights)
+                    .map(|(objective, weight)| format!("{objective} × {weight}"))
+                    .join(", ")
+            ));
+
+            let rows = ranking
+                .ranked
+                .iter()
+                .enumerate()
+                .map(|(rank, ranked)| {
+                    [
+                        vec![(rank + 1).to_string()],
+                        config_columns(&ranked.entry),
+                        objective_columns(&ranked.entry),
+                        vec![format!("{:.3}", ranked.score)],
+                    ]
+                    .concat()
+                })
+                .collect::<Vec<_>>();
+            markdown.push_str(&markdown_table(&ranked_header, &rows));
+        }
+
+        markdown
+    }
+}
+
+/// Formats a table in the same style as the tables in the README.
+pub fn markdown_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut table = format!("|{}|\n", header.join("|"));
+    table.push_str(&format!("|{}|\n", vec!["-"; header.len()].join("|")));
+
+    for row in rows {
+        table.push_str(&format!("|{}|\n", row.join("|")));
+    }
+
+    table
+}
+
+pub fn format_ms(ms: Option<f64>) -> String {
+    ms.map(|ms| format!("{ms:.2}ms")).unwrap_or_default()
+}
+
+pub fn format_secs(secs: Option<f64>) -> String {
+    secs.map(|secs| format!("{secs:.1}s")).unwrap_or_default()
+}
+
+pub fn format_size(bytes: Option<f64>) -> String {
+    bytes
+        .map(|bytes| Size::from_bytes(bytes).to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,634 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use strum::IntoEnumIterator;
+
+use crate::build::Artifact;
+use crate::compress::Codec;
+use crate::database::Database;
+use crate::measure::{FrameStats, LoadTimes, Session};
+use crate::metadata::{self, RunMetadata};
+use crate::options::*;
+use crate::stats::Summary;
+
+/// Version of the results schema this runner writes, recorded in the metadata file next to the
+/// results:
+///
+/// 1. The original columns, from before the `panic` option.
+/// 2. Adds `panic`.
+/// 3. Adds frame time statistics across repetitions, frame time percentiles, load times, sizes
+///    for more codecs and `contended`.
+/// 4. Adds `contended_samples`, and `contended` in the samples file.
+pub const SCHEMA_VERSION: u32 = 4;
+
+pub const CSV_HEADER: &str = "opt_level,wasm_opt,lto,codegen_units,strip,panic,build_time,wasm_opt_time,size,size_gzipped,size_gzip_best,size_brotli_9,size_brotli_11,size_zstd_19,frame_time,frame_time_median,frame_time_stddev,frame_time_min,frame_time_max,frame_time_ci_low,frame_time_ci_high,samples,frame_p50,frame_p90,frame_p95,frame_p99,frame_max,frames_over_budget,frames,frame_histogram,wasm_download_time,wasm_compile_time,wasm_instantiate_time,startup_time,first_frame_time,contended,contended_samples";
+/// Header of the side file holding every individual frame time sample.
+pub const SAMPLES_HEADER: &str =
+    "opt_level,wasm_opt,lto,codegen_units,strip,panic,repetition,frame_time,frame_p50,frame_p90,frame_p95,frame_p99,frame_max,frames_over_budget,frames,frame_histogram,wasm_download_time,wasm_compile_time,wasm_instantiate_time,startup_time,first_frame_time,contended";
+
+/// Appended to a results file's name for its samples file.
+pub const SAMPLES_SUFFIX: &str = "-samples.csv";
+
+/// The side file holding individual samples for a results file, like `out-samples.csv` for
+/// `out.csv`.
+pub fn samples_path(csv_path: &Path) -> PathBuf {
+    let stem = csv_path.file_stem().unwrap_or_default().to_string_lossy();
+    csv_path.with_file_name(format!("{stem}{SAMPLES_SUFFIX}"))
+}
+
+/// The directory holding a wasm size breakdown of each build for a results file, like
+/// `out-sizes` for `out.csv`.
+pub fn sizes_dir(csv_path: &Path) -> PathBuf {
+    let stem = csv_path.file_stem().unwrap_or_default().to_string_lossy();
+    csv_path.with_file_name(format!("{stem}-sizes"))
+}
+
+/// Whether `path` is a samples file rather than a results file.
+pub fn is_samples_path(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(SAMPLES_SUFFIX)
+}
+
+/// Creates a new results file at `path`, truncating any existing one, and writes `header`.
+pub fn create(path: &Path, header: &str) -> Result<File> {
+    let mut csv = File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+    writeln!(csv, "{}", header)?;
+
+    Ok(csv)
+}
+
+/// Opens an existing results file for appending and returns the configurations it already
+/// contains.
+///
+/// An incomplete final row, left behind if the runner was killed mid-write, is removed.
+pub fn resume(path: &Path) -> Result<(File, HashSet<Config>)> {
+    let (rows, complete_len, len) = read_rows(path, CSV_HEADER)?;
+    let done = rows.into_iter().collect::<HashSet<_>>();
+
+    let csv = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Opening {}", path.display()))?;
+
+    if complete_len < len {
+        csv.set_len(complete_len as u64)?;
+    }
+
+    Ok((csv, done))
+}
+
+/// The configurations already in a results file, without modifying it.
+pub fn completed(path: &Path) -> Result<HashSet<Config>> {
+    let (rows, _, _) = read_rows(path, CSV_HEADER)?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// A row of a results file, with its values looked up by column name.
+pub struct Row {
+    pub config: Config,
+    values: HashMap<String, String>,
+}
+
+impl Row {
+    /// The number in a column, or `None` if the column is missing, empty or not a number.
+    pub fn value(&self, column: &str) -> Option<f64> {
+        self.values.get(column)?.parse().ok()
+    }
+}
+
+/// Reads every complete row of a results file of any schema version, upgraded to the current
+/// one as described on [`Record`].
+pub fn read_results(path: &Path) -> Result<Vec<Row>> {
+    let columns = CSV_HEADER.split(',').collect::<Vec<_>>();
+
+    Ok(read_records(path)?
+        .into_iter()
+        .map(|record| Row {
+            config: record.config,
+            values: columns
+                .iter()
+                .map(|column| column.to_string())
+                .zip(record.to_csv_row().split(',').map(str::to_string))
+                .collect(),
+        })
+        .collect())
+}
+
+/// A row of a results file in the current schema.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub config: Config,
+    pub build_time: Duration,
+    pub wasm_opt_time: Duration,
+    pub size: u64,
+    /// Compressed size for each codec that was measured.
+    pub compressed_sizes: Vec<(Codec, u64)>,
+    /// Mean frame time of each browser session.
+    pub frame_time: Summary,
+    /// Frame time percentiles and counts, combined across sessions.
+    pub frames: Option<FrameStats>,
+    /// Load times, averaged across sessions.
+    pub load: Option<LoadTimes>,
+    /// Whether the build or wasm-opt pass overlapped with other work. `None` before version 3.
+    pub contended: Option<bool>,
+    /// Number of browser sessions during which a build or wasm-opt pass was still running.
+    /// `None` before version 4.
+    pub contended_samples: Option<usize>,
+}
+
+impl Record {
+    /// The record for an artifact, given the measurements from each of its browser sessions.
+    pub fn new(artifact: &Artifact, sessions: &[Session]) -> Record {
+        let combined = Session::combine(sessions);
+
+        Record {
+            config: artifact.config,
+            build_time: artifact.build_time,
+            wasm_opt_time: artifact.wasm_opt_time,
+            size: artifact.size,
+            compressed_sizes: artifact.compressed_sizes.clone(),
+            frame_time: Summary::new(
+                &sessions
+                    .iter()
+                    .map(|s| s.frames.average)
+                    .collect::<Vec<_>>(),
+            ),
+            frames: Some(combined.frames),
+            load: Some(combined.load),
+            contended: Some(artifact.contended),
+            contended_samples: Some(sessions.iter().filter(|s| s.contended).count()),
+        }
+    }
+
+    /// Reads a row of a file with the given schema version, by column name.
+    ///
+    /// Values older versions didn't record are filled in: `panic` is `Unwind` before version 2,
+    /// and before version 3 the frame time statistics are those of a single session. Frame stats,
+    /// load times, compressed sizes and contention that weren't recorded are `None`.
+    fn parse(values: &HashMap<&str, &str>, version: u32) -> Result<Record> {
+        let config = Config {
+            cargo: CargoOptions {
+                opt_level: parse(values, "opt_level")?,
+                lto: parse(values, "lto")?,
+                codegen_units: parse(values, "codegen_units")?,
+                strip: parse(values, "strip")?,
+                panic: if version >= 2 {
+                    parse(values, "panic")?
+                } else {
+                    Panic::Unwind
+                },
+            },
+            wasm_opt: parse(values, "wasm_opt")?,
+        };
+
+        let mut compressed_sizes = vec![];
+        for codec in Codec::iter() {
+            if let Some(size) = parse_optional(values, codec.column())? {
+                compressed_sizes.push((codec, size));
+            }
+        }
+
+        let frame_time = if version >= 3 {
+            Summary {
+                n: parse(values, "samples")?,
+                mean: parse(values, "frame_time")?,
+                median: parse(values, "frame_time_median")?,
+                stddev: parse_optional(values, "frame_time_stddev")?.unwrap_or(f64::NAN),
+                min: parse(values, "frame_time_min")?,
+                max: parse(values, "frame_time_max")?,
+                ci_low: parse_optional(values, "frame_time_ci_low")?.unwrap_or(f64::NAN),
+                ci_high: parse_optional(values, "frame_time_ci_high")?.unwrap_or(f64::NAN),
+            }
+        } else {
+            Summary::new(&[parse(values, "frame_time")?])
+        };
+
+        let frames = match parse_optional(values, "frame_p50")? {
+            Some(p50) => Some(FrameStats {
+                average: frame_time.mean,
+                p50,
+                p90: parse(values, "frame_p90")?,
+                p95: parse(values, "frame_p95")?,
+                p99: parse(values, "frame_p99")?,
+                max: parse(values, "frame_max")?,
+                over_budget: parse(values, "frames_over_budget")?,
+                frames: parse(values, "frames")?,
+                histogram: parse_histogram(values.get("frame_histogram").copied().unwrap_or(""))?,
+            }),
+            None => None,
+        };
+
+        // Phases missing from the browser's timing data are left empty, so load times are only
+        // missing altogether when every phase is.
+        let load = LoadTimes {
+            download: parse_optional(values, "wasm_download_time")?.unwrap_or(f64::NAN),
+            compile: parse_optional(values, "wasm_compile_time")?.unwrap_or(f64::NAN),
+            instantiate: parse_optional(values, "wasm_instantiate_time")?.unwrap_or(f64::NAN),
+            startup: parse_optional(values, "startup_time")?.unwrap_or(f64::NAN),
+            first_frame: parse_optional(values, "first_frame_time")?.unwrap_or(f64::NAN),
+        };
+        let load = (!load.is_empty()).then_some(load);
+
+        Ok(Record {
+            config,
+            build_time: parse_secs(values, "build_time")?,
+            wasm_opt_time: parse_secs(values, "wasm_opt_time")?,
+            size: parse(values, "size")?,
+            compressed_sizes,
+            frame_time,
+            frames,
+            load,
+            contended: parse_optional(values, "contended")?,
+            contended_samples: parse_optional(values, "contended_samples")?,
+        })
+    }
+
+    /// The record's values in the columns of [`CSV_HEADER`]. Values that weren't recorded are
+    /// left empty.
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            config_columns(&self.config),
+            self.build_time.as_secs_f32(),
+            self.wasm_opt_time.as_secs_f32(),
+            self.size,
+            size_columns(&self.compressed_sizes),
+            self.frame_time.mean,
+            self.frame_time.median,
+            optional_number(self.frame_time.stddev),
+            self.frame_time.min,
+            self.frame_time.max,
+            optional_number(self.frame_time.ci_low),
+            optional_number(self.frame_time.ci_high),
+            self.frame_time.n,
+            self.frames
+                .as_ref()
+                .map(frame_stats_columns)
+                .unwrap_or_else(|| empty_columns(8)),
+            self.load
+                .as_ref()
+                .map(load_times_columns)
+                .unwrap_or_else(|| empty_columns(5)),
+            self.contended
+                .map(|contended| contended.to_string())
+                .unwrap_or_default(),
+            self.contended_samples
+                .map(|count| count.to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// Reads every complete row of a results file of any schema version. See [`Record::parse`] for
+/// how older versions are upgraded.
+pub fn read_records(path: &Path) -> Result<Vec<Record>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let mut lines = contents.lines();
+
+    let header = lines
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .collect::<Vec<_>>();
+    let version = schema_version_of(path, &header)?;
+
+    let mut records = vec![];
+
+    for (i, line) in lines.enumerate() {
+        let fields = line.split(',').collect::<Vec<_>>();
+        if fields.len() != header.len() {
+            // An incomplete final row.
+            continue;
+        }
+
+        let values = header
+            .iter()
+            .copied()
+            .zip(fields)
+            .collect::<HashMap<_, _>>();
+        let record = Record::parse(&values, version)
+            .with_context(|| format!("Parsing row {} of {}", i + 1, path.display()))?;
+
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// The schema version of a results file.
+pub fn schema_version(path: &Path) -> Result<u32> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let header = contents
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .collect::<Vec<_>>();
+
+    schema_version_of(path, &header)
+}
+
+/// The schema version recorded in a results file's metadata file, or for files from before
+/// versions were recorded, the version its columns belong to.
+fn schema_version_of(path: &Path, header: &[&str]) -> Result<u32> {
+    let metadata_path = metadata::metadata_path(path);
+    let recorded = if metadata_path.exists() {
+        RunMetadata::load(&metadata_path)?.schema_version
+    } else {
+        None
+    };
+
+    let version = recorded.unwrap_or(if !header.contains(&"panic") {
+        1
+    } else if !header.contains(&"frame_time_median") {
+        2
+    } else if !header.contains(&"contended_samples") {
+        3
+    } else {
+        4
+    });
+
+    if version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "{} has schema version {version}, but this runner only reads up to version {SCHEMA_VERSION}",
+            path.display()
+        );
+    }
+
+    Ok(version)
+}
+
+/// Opens the samples file for appending, keeping only the samples of configurations in `done`.
+///
+/// Samples of configurations that weren't finished are discarded, since those configurations
+/// will be measured again from scratch.
+pub fn resume_samples(path: &Path, done: &HashSet<Config>) -> Result<File> {
+    if !path.exists() {
+        return create(path, SAMPLES_HEADER);
+    }
+
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let (rows, _, _) = read_rows(path, SAMPLES_HEADER)?;
+
+    let mut csv = create(path, SAMPLES_HEADER)?;
+    for (config, line) in rows.iter().zip(contents.lines().skip(1)) {
+        if done.contains(config) {
+            writeln!(csv, "{}", line)?;
+        }
+    }
+
+    Ok(csv)
+}
+
+/// Reads every complete row of a samples file, in the format of [`SAMPLES_HEADER`]. Rows from
+/// before schema version 4 are given an empty `contended` column.
+pub fn read_samples(path: &Path) -> Result<Vec<String>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let mut lines = contents.lines();
+
+    let header = lines.next().unwrap_or_default();
+    let padding = if header == SAMPLES_HEADER {
+        ""
+    } else if Some(header) == SAMPLES_HEADER.strip_suffix(",contended") {
+        ","
+    } else {
+        anyhow::bail!("{} has an unexpected header", path.display());
+    };
+
+    let columns = header.split(',').count();
+
+    Ok(lines
+        .filter(|line| line.split(',').count() == columns)
+        .map(|line| format!("{line}{padding}"))
+        .collect())
+}
+
+/// Where a run writes its results: the results and samples files, and optionally a run in a
+/// results database.
+pub struct Output {
+    pub csv: File,
+    pub samples_csv: File,
+    pub database: Option<(Database, i64)>,
+}
+
+impl Output {
+    /// Writes the results row for an artifact, given the measurements from each of its
+    /// browser sessions.
+    pub fn write_row(&mut self, artifact: &Artifact, sessions: &[Session]) -> Result<()> {
+        let row = Record::new(artifact, sessions).to_csv_row();
+
+        writeln!(self.csv, "{}", row).context("Writing results")?;
+        self.csv.flush()?;
+
+        if let Some((database, run)) = &self.database {
+            database.insert_result(*run, &row)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_sample(
+        &mut self,
+        config: &Config,
+        repetition: usize,
+        session: &Session,
+    ) -> Result<()> {
+        let row = format!(
+            "{},{},{},{},{},{}",
+            config_columns(config),
+            repetition,
+            session.frames.average,
+            frame_stats_columns(&session.frames),
+            load_times_columns(&session.load),
+            session.contended
+        );
+
+        writeln!(self.samples_csv, "{}", row).context("Writing sample")?;
+        self.samples_csv.flush()?;
+
+        if let Some((database, run)) = &self.database {
+            database.insert_sample(*run, &row)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn frame_stats_columns(stats: &FrameStats) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{}",
+        stats.p50,
+        stats.p90,
+        stats.p95,
+        stats.p99,
+        stats.max,
+        stats.over_budget,
+        stats.frames,
+        stats.histogram_string()
+    )
+}
+
+/// Compressed sizes for every codec, in the order of [`Codec`]'s variants. Codecs that weren't
+/// measured are left empty.
+fn size_columns(compressed_sizes: &[(Codec, u64)]) -> String {
+    Codec::iter()
+        .map(|codec| {
+            compressed_sizes
+                .iter()
+                .find(|(c, _)| *c == codec)
+                .map(|(_, size)| size.to_string())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn load_times_columns(load: &LoadTimes) -> String {
+    format!(
+        "{},{},{},{},{}",
+        optional_number(load.download),
+        optional_number(load.compile),
+        optional_number(load.instantiate),
+        optional_number(load.startup),
+        optional_number(load.first_frame)
+    )
+}
+
+/// Formats a value that may be `NaN`, like a standard deviation of one sample, leaving it
+/// empty so that it reads as missing.
+fn optional_number(value: f64) -> String {
+    if value.is_nan() {
+        String::new()
+    } else {
+        value.to_string()
+    }
+}
+
+fn empty_columns(count: usize) -> String {
+    ",".repeat(count - 1)
+}
+
+fn config_columns(config: &Config) -> String {
+    format!(
+        "{:?},{:?},{:?},{:?},{:?},{:?}",
+        config.cargo.opt_level,
+        config.wasm_opt,
+        config.cargo.lto,
+        config.cargo.codegen_units,
+        config.cargo.strip,
+        config.cargo.panic
+    )
+}
+
+fn parse<T: FromStr>(values: &HashMap<&str, &str>, name: &str) -> Result<T> {
+    let value = values
+        .get(name)
+        .copied()
+        .filter(|value| !value.is_empty())
+        .with_context(|| format!("Missing {name}"))?;
+
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid {name}: {value}"))
+}
+
+/// Like [`parse`], but `None` for a missing or empty column.
+fn parse_optional<T: FromStr>(values: &HashMap<&str, &str>, name: &str) -> Result<Option<T>> {
+    match values.get(name) {
+        Some(value) if !value.is_empty() => parse(values, name).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Parses a duration in seconds.
+fn parse_secs(values: &HashMap<&str, &str>, name: &str) -> Result<Duration> {
+    Duration::try_from_secs_f32(parse(values, name)?).with_context(|| format!("Invalid {name}"))
+}
+
+/// Parses a histogram written by [`FrameStats::histogram_string`].
+fn parse_histogram(histogram: &str) -> Result<Vec<(String, u64)>> {
+    histogram
+        .split(';')
+        .filter(|bucket| !bucket.is_empty())
+        .map(|bucket| {
+            let (label, count) = bucket
+                .rsplit_once(':')
+                .with_context(|| format!("Invalid histogram bucket: {bucket}"))?;
+
+            Ok((label.to_string(), count.parse()?))
+        })
+        .collect()
+}
+
+/// Reads the configuration of every complete row in a CSV file with the given header.
+///
+/// Also returns the length in bytes of the complete rows and of the whole file.
+fn read_rows(path: &Path, expected_header: &str) -> Result<(Vec<Config>, usize, usize)> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+
+    let mut lines = contents.split_inclusive('\n');
+
+    let header = lines.next().unwrap_or_default();
+    if header.trim_end() != expected_header {
+        anyhow::bail!(
+            "{} has an unexpected header and can't be resumed.\nExpected: {}\nFound:    {}",
+            path.display(),
+            expected_header,
+            header.trim_end()
+        );
+    }
+
+    let columns = expected_header.split(',').count();
+    let mut rows = vec![];
+    let mut complete_len = header.len();
+
+    for (i, line) in lines.enumerate() {
+        if !line.ends_with('\n') {
+            println!("Dropping incomplete final row in {}.", path.display());
+            break;
+        }
+
+        let config = parse_config(line.trim_end(), columns)
+            .with_context(|| format!("Parsing row {} of {}", i + 1, path.display()))?;
+        rows.push(config);
+
+        complete_len += line.len();
+    }
+
+    Ok((rows, complete_len, contents.len()))
+}
+
+/// Parses the configuration columns of a results row.
+pub fn parse_config(row: &str, columns: usize) -> Result<Config> {
+    let fields = row.split(',').collect::<Vec<_>>();
+    if fields.len() != columns {
+        anyhow::bail!("Expected {} columns, found {}", columns, fields.len());
+    }
+
+    fn parse<T: FromStr>(name: &str, value: &str) -> Result<T> {
+        value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid {name}: {value}"))
+    }
+
+    Ok(Config {
+        cargo: CargoOptions {
+            opt_level: parse("opt_level", fields[0])?,
+            lto: parse("lto", fields[2])?,
+            codegen_units: parse("codegen_units", fields[3])?,
+            strip: parse("strip", fields[4])?,
+            panic: parse("panic", fields[5])?,
+        },
+        wasm_opt: parse("wasm_opt", fields[1])?,
+    })
+}
@@ -0,0 +1,288 @@
+//! Adaptive search for the configurations with the best tradeoff between frame time, size and
+//! build time.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::Result;
+use rand::seq::SliceRandom;
+
+use crate::build::Artifact;
+use crate::cli::SearchArgs;
+use crate::compress::Codec;
+use crate::database::Database;
+use crate::measure::Session;
+use crate::metadata::{self, RunMetadata};
+use crate::options::Config;
+use crate::pipeline::{self, Activity};
+use crate::report::{format_ms, format_secs, format_size, markdown_table};
+use crate::results::{self, Output, CSV_HEADER, SAMPLES_HEADER};
+use crate::{pareto, OUT_DIR};
+
+/// Where `search` writes results by default.
+pub const SEARCH_CSV_PATH: &str = "search.csv";
+
+/// A configuration that has been built, with every session measured so far.
+struct Candidate {
+    artifact: Artifact,
+    sessions: Vec<Session>,
+}
+
+impl Candidate {
+    /// Mean frame time, gzipped size and total build time, all to be minimized.
+    fn objectives(&self) -> Vec<f64> {
+        let frame_time = self
+            .sessions
+            .iter()
+            .map(|session| session.frames.average)
+            .sum::<f64>()
+            / self.sessions.len() as f64;
+
+        vec![
+            frame_time,
+            self.artifact
+                .compressed_size(Codec::Gzip)
+                .unwrap_or_default() as f64,
+            (self.artifact.build_time + self.artifact.wasm_opt_time).as_secs_f64(),
+        ]
+    }
+}
+
+/// Measures every configuration, then repeatedly keeps the better half by Pareto layer and
+/// doubles their repetitions, until the Pareto front stops changing between rounds or the
+/// repetition limit is reached.
+///
+/// Every configuration is written to the results file as soon as it's first measured, and
+/// updated with however many sessions it got at the end of each round. The final front is
+/// printed.
+pub fn search(mut args: SearchArgs) -> Result<()> {
+    args.output = std::path::absolute(&args.output)?;
+    args.matrix.matrix = std::path::absolute(&args.matrix.matrix)?;
+    args.database = args.database.map(std::path::absolute).transpose()?;
+    args.build.sizes = args.build.sizes.map(std::path::absolute).transpose()?;
+    crate::enter_workspace()?;
+
+    let (configs, design) = args.matrix.configs()?;
+    let max_repetitions = args.max_repetitions as usize;
+    let mut repetitions = (args.measure.repetitions as usize).min(max_repetitions);
+
+    crate::deps::check_all_deps()?;
+    let options = crate::measure_options(&args.measure, OUT_DIR.into())?;
+    println!();
+
+    crate::copy_assets()?;
+
+    println!("Collecting environment metadata.");
+    let metadata = RunMetadata::collect(&options.browser, design);
+
+    if let Some(dir) = args.output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    metadata.write(&metadata::metadata_path(&args.output))?;
+    let mut output = Output {
+        csv: results::create(&args.output, CSV_HEADER)?,
+        samples_csv: results::create(&results::samples_path(&args.output), SAMPLES_HEADER)?,
+        database: args
+            .database
+            .as_ref()
+            .map(|path| Database::open_run(path, &args.output, &metadata, false))
+            .transpose()?,
+    };
+
+    let activity = Activity::default();
+    let mut candidates: Vec<Candidate> = vec![];
+    let num_configs = configs.len();
+
+    // Build every configuration and measure it with the starting repetitions.
+
+    pipeline::run(
+        crate::group_builds(&configs),
+        args.build.jobs,
+        &args.build.options(&args.output),
+        &activity,
+        |artifact| {
+            println!();
+            println!(
+                "Configuration {}/{}: {} with WasmOpt::{:?}",
+                candidates.len() + 1,
+                num_configs,
+                artifact.config.cargo.name(),
+                artifact.config.wasm_opt
+            );
+
+            let mut sessions = vec![];
+            for repetition in 0..repetitions {
+                let session = crate::measure(&artifact, &options, args.measure.retries, &activity)?;
+                output.write_sample(&artifact.config, repetition, &session)?;
+                sessions.push(session);
+            }
+
+            output.write_row(&artifact, &sessions)?;
+            candidates.push(Candidate { artifact, sessions });
+            Ok(())
+        },
+    )?;
+
+    // Narrow down to the front, measuring the survivors more each round.
+
+    let mut survivors = (0..candidates.len()).collect::<Vec<_>>();
+    let mut previous_front = HashSet::new();
+    let mut round = 1;
+
+    let front = loop {
+        let points = survivors
+            .iter()
+            .map(|&i| candidates[i].objectives())
+            .collect::<Vec<_>>();
+        let layers = pareto::layers(&points);
+
+        let front = survivors
+            .iter()
+            .zip(&layers)
+            .filter(|(_, layer)| **layer == 0)
+            .map(|(&i, _)| i)
+            .collect::<Vec<_>>();
+        let front_configs = front
+            .iter()
+            .map(|&i| candidates[i].artifact.config)
+            .collect::<HashSet<Config>>();
+
+        println!();
+        println!(
+            "Round {round}: {} candidates with {repetitions} repetitions, {} on the Pareto front.",
+            survivors.len(),
+            front.len()
+        );
+
+        if front_configs == previous_front {
+            println!("The front didn't change since the last round.");
+            break front;
+        }
+        if repetitions >= max_repetitions {
+            println!("Reached {max_repetitions} repetitions.");
+            break front;
+        }
+
+        // Keep the better half, ranked by layer and then frame time, but always the whole front.
+
+        let mut ranked = survivors
+            .iter()
+            .zip(layers)
+            .zip(&points)
+            .map(|((&i, layer), objectives)| (i, layer, objectives[0]))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(_, a_layer, a_frame_time), (_, b_layer, b_frame_time)| {
+            a_layer
+                .cmp(b_layer)
+                .then(a_frame_time.total_cmp(b_frame_time))
+        });
+        survivors = ranked
+            .into_iter()
+            .take(survivors.len().div_ceil(2).max(front.len()))
+            .map(|(i, _, _)| i)
+            .collect();
+
+        repetitions = (repetitions * 2).min(max_repetitions);
+
+        // Take the extra repetitions in a shuffled order, for the same reason as in `run`.
+
+        let mut order = survivors
+            .iter()
+            .flat_map(|&i| std::iter::repeat_n(i, repetitions - candidates[i].sessions.len()))
+            .collect::<Vec<_>>();
+        order.shuffle(&mut rand::thread_rng());
+
+        for (n, i) in order.iter().copied().enumerate() {
+            let candidate = &mut candidates[i];
+
+            println!();
+            println!(
+                "Round {} repetition {}/{}: {} with WasmOpt::{:?} (sample {}/{})",
+                round + 1,
+                n + 1,
+                order.len(),
+                candidate.artifact.config.cargo.name(),
+                candidate.artifact.config.wasm_opt,
+                candidate.sessions.len() + 1,
+                repetitions
+            );
+
+            let session = crate::measure(
+                &candidate.artifact,
+                &options,
+                args.measure.retries,
+                &activity,
+            )?;
+            output.write_sample(
+                &candidate.artifact.config,
+                candidate.sessions.len(),
+                &session,
+            )?;
+            candidate.sessions.push(session);
+        }
+
+        write_rows(&mut output, &args.output, &candidates)?;
+
+        previous_front = front_configs;
+        round += 1;
+    };
+
+    print_front(&candidates, front, &args.output);
+
+    Ok(())
+}
+
+/// Rewrites the results file, and the database if there is one, with every candidate's sessions
+/// so far, so that an interrupted search leaves complete results for the rounds it finished.
+fn write_rows(output: &mut Output, path: &Path, candidates: &[Candidate]) -> Result<()> {
+    output.csv = results::create(path, CSV_HEADER)?;
+    for candidate in candidates {
+        output.write_row(&candidate.artifact, &candidate.sessions)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the non-dominated configurations, fastest first.
+fn print_front(candidates: &[Candidate], mut front: Vec<usize>, output: &Path) {
+    front.sort_by(|a, b| candidates[*a].objectives()[0].total_cmp(&candidates[*b].objectives()[0]));
+
+    let table = front
+        .iter()
+        .map(|&i| {
+            let candidate = &candidates[i];
+            let objectives = candidate.objectives();
+
+            vec![
+                candidate.artifact.config.cargo.name(),
+                format!("{:?}", candidate.artifact.config.wasm_opt),
+                format_ms(Some(objectives[0])),
+                format_size(Some(objectives[1])),
+                format_secs(Some(objectives[2])),
+                candidate.sessions.len().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    println!();
+    println!(
+        "{} configurations aren't beaten in frame time, gzipped size and build time by any other:",
+        front.len()
+    );
+    println!();
+    println!(
+        "{}",
+        markdown_table(
+            &[
+                "cargo",
+                "wasm_opt",
+                "frame_time",
+                "size_gzipped",
+                "build_time + wasm_opt_time",
+                "samples"
+            ],
+            &table
+        )
+    );
+    println!("Every measured configuration is in {}.", output.display());
+}
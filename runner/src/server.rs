@@ -0,0 +1,210 @@
+//! A small static file server for the bench page.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves the files in a directory over HTTP on an ephemeral port until stopped or dropped.
+pub struct Server {
+    address: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Starts serving `root` on `127.0.0.1`.
+    ///
+    /// With `precompressed`, requests for a file are answered with a `.br` or `.gz` sibling
+    /// of that file when one exists and the client accepts that encoding.
+    pub fn start<P: Into<PathBuf>>(root: P, precompressed: bool) -> Result<Self> {
+        let root = root.into();
+        let listener = TcpListener::bind("127.0.0.1:0").context("Binding web server")?;
+        let address = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let shutdown = shutdown.clone();
+
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+
+                    let root = root.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, &root, precompressed) {
+                            eprintln!("Web server error: {e:#}");
+                        }
+                    });
+                }
+            })
+        };
+
+        Ok(Self {
+            address,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.address)
+    }
+
+    /// Stops accepting connections and waits for the server thread to exit.
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown_and_join()
+    }
+
+    fn shutdown_and_join(&mut self) -> Result<()> {
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so that it notices the shutdown flag.
+        let _ = TcpStream::connect(self.address);
+
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("Web server thread panicked"))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.shutdown_and_join();
+    }
+}
+
+fn handle_connection(stream: TcpStream, root: &Path, precompressed: bool) -> Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut accept_encoding = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("accept-encoding") {
+                accept_encoding = value.trim().to_ascii_lowercase();
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond_status(&mut stream, "400 Bad Request");
+    };
+
+    if method != "GET" && method != "HEAD" {
+        return respond_status(&mut stream, "405 Method Not Allowed");
+    }
+
+    let Some(path) = resolve_path(root, target) else {
+        return respond_status(&mut stream, "404 Not Found");
+    };
+
+    let mut body_path = path.clone();
+    let mut content_encoding = None;
+
+    if precompressed {
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            let compressed = PathBuf::from(format!("{}.{}", path.display(), extension));
+            let accepted = accept_encoding
+                .split(',')
+                .any(|accepted| accepted.split(';').next().unwrap_or_default().trim() == encoding);
+
+            if accepted && compressed.is_file() {
+                body_path = compressed;
+                content_encoding = Some(encoding);
+                break;
+            }
+        }
+    }
+
+    let body = std::fs::read(&body_path)?;
+
+    let mut headers = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n",
+        mime_type(&path),
+        body.len()
+    );
+    if precompressed {
+        headers.push_str("Vary: Accept-Encoding\r\n");
+    }
+    if let Some(encoding) = content_encoding {
+        headers.push_str(&format!("Content-Encoding: {encoding}\r\n"));
+    }
+    headers.push_str("\r\n");
+
+    stream.write_all(headers.as_bytes())?;
+    if method == "GET" {
+        stream.write_all(&body)?;
+    }
+    stream.flush()?;
+
+    Ok(())
+}
+
+fn respond_status(stream: &mut TcpStream, status: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    )?;
+
+    Ok(())
+}
+
+/// Maps a request target to a file under `root`, rejecting anything that would escape it.
+fn resolve_path(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next()?;
+
+    let mut resolved = root.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if resolved.is_dir() {
+        resolved.push("index.html");
+    }
+
+    resolved.is_file().then_some(resolved)
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("wasm") => "application/wasm",
+        Some("json") => "application/json",
+        Some("css") => "text/css; charset=utf-8",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,235 @@
+//! Basic descriptive statistics and the Student's t distribution.
+
+/// Summary statistics for a set of repeated measurements.
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub n: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation. `NaN` with fewer than two samples.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Bounds of the 95% confidence interval for the mean. `NaN` with fewer than two samples.
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+impl Summary {
+    /// Summarizes `samples`, which must not be empty.
+    pub fn new(samples: &[f64]) -> Self {
+        assert!(!samples.is_empty(), "Can't summarize zero samples");
+
+        let n = samples.len();
+        let mean = mean(samples);
+        let stddev = stddev(samples);
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let half_width = if n > 1 {
+            t_quantile(0.975, (n - 1) as f64) * stddev / (n as f64).sqrt()
+        } else {
+            f64::NAN
+        };
+
+        Self {
+            n,
+            mean,
+            median: quantile_sorted(&sorted, 0.5),
+            stddev,
+            min: sorted[0],
+            max: sorted[n - 1],
+            ci_low: mean - half_width,
+            ci_high: mean + half_width,
+        }
+    }
+}
+
+pub fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// Sample variance, with Bessel's correction. `NaN` with fewer than two samples.
+pub fn variance(samples: &[f64]) -> f64 {
+    if samples.len() < 2 {
+        return f64::NAN;
+    }
+
+    let mean = mean(samples);
+    samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples.len() - 1) as f64
+}
+
+pub fn stddev(samples: &[f64]) -> f64 {
+    variance(samples).sqrt()
+}
+
+/// Linearly interpolated quantile of already sorted samples, with `q` in `[0, 1]`.
+pub fn quantile_sorted(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// Cumulative distribution function of Student's t distribution with `df` degrees of freedom.
+pub fn t_cdf(t: f64, df: f64) -> f64 {
+    let x = df / (df + t * t);
+    let tail = 0.5 * regularized_incomplete_beta(x, df / 2.0, 0.5);
+
+    if t > 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+/// Two-sided p-value of a t statistic with `df` degrees of freedom.
+///
+/// Computed from the incomplete beta function directly rather than from [`t_cdf`], so that
+/// very small p-values don't round to zero.
+pub fn t_p_value(t: f64, df: f64) -> f64 {
+    regularized_incomplete_beta(df / (df + t * t), df / 2.0, 0.5)
+}
+
+/// Two-sided p-value of Welch's t-test for a difference between the means of two groups,
+/// given each group's mean, sample standard deviation and size.
+pub fn welch_p_value(a: (f64, f64, f64), b: (f64, f64, f64)) -> f64 {
+    let ((mean_a, stddev_a, n_a), (mean_b, stddev_b, n_b)) = (a, b);
+    let (var_a, var_b) = (stddev_a.powi(2) / n_a, stddev_b.powi(2) / n_b);
+    if var_a + var_b == 0.0 {
+        return if mean_a == mean_b { 1.0 } else { 0.0 };
+    }
+
+    let t = (mean_a - mean_b) / (var_a + var_b).sqrt();
+    let df = (var_a + var_b).powi(2) / (var_a.powi(2) / (n_a - 1.0) + var_b.powi(2) / (n_b - 1.0));
+
+    t_p_value(t, df)
+}
+
+/// Upper tail probability of an F statistic with `d1` and `d2` degrees of freedom.
+pub fn f_p_value(f: f64, d1: f64, d2: f64) -> f64 {
+    regularized_incomplete_beta(d2 / (d2 + d1 * f), d2 / 2.0, d1 / 2.0)
+}
+
+/// Inverse of [`t_cdf`], found by bisection.
+pub fn t_quantile(p: f64, df: f64) -> f64 {
+    let (mut low, mut high) = (-1e3, 1e3);
+
+    for _ in 0..200 {
+        let mid = 0.5 * (low + high);
+        if t_cdf(mid, df) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    0.5 * (low + high)
+}
+
+/// The regularized incomplete beta function `I_x(a, b)`.
+///
+/// Uses the continued fraction from Numerical Recipes, section 6.4.
+pub fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    let front = ln_front.exp();
+
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+
+    let qab = a + b;
+    let qap = a + 1.0;
+    let qam = a - 1.0;
+
+    let mut c = 1.0;
+    let mut d = 1.0 - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    h
+}
+
+/// Natural log of the gamma function, using the Lanczos approximation.
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+    let t = x + 7.5;
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
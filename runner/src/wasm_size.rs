@@ -0,0 +1,212 @@
+//! Breaks the size of a wasm module down by section, function and crate.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use wasmparser::{KnownCustom, Name, Parser, Payload};
+
+/// Number of functions listed in [`SizeReport::top_functions`].
+const TOP_FUNCTIONS: usize = 100;
+
+/// Where the bytes of a wasm module go.
+#[derive(Serialize, Debug)]
+pub struct SizeReport {
+    /// Size of the whole module.
+    pub total: u64,
+    /// Bytes in the code section.
+    pub code: u64,
+    /// Bytes in the data section.
+    pub data: u64,
+    /// Bytes in the `name` custom section.
+    pub names: u64,
+    /// Bytes in DWARF `.debug_*` custom sections.
+    pub debug_info: u64,
+    /// Bytes in all custom sections, including `names` and `debug_info`.
+    pub custom: u64,
+    /// The contents of every section, in the order they appear. Section headers aren't counted,
+    /// so these add up to a little less than `total`.
+    pub sections: Vec<SectionSize>,
+    pub function_count: usize,
+    /// The largest function bodies, largest first.
+    pub top_functions: Vec<FunctionSize>,
+    /// Function body bytes per crate, largest first.
+    pub crates: Vec<CrateSize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SectionSize {
+    /// The section's kind, like `code`, or its name for custom sections, like `.debug_info`.
+    pub name: String,
+    pub custom: bool,
+    pub size: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FunctionSize {
+    /// Demangled name without the hash, or `func[<index>]` if the module has no name for it.
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CrateSize {
+    /// The crate a function was attributed to, or `(unknown)` for unnamed and non-Rust
+    /// functions.
+    pub name: String,
+    pub functions: usize,
+    pub size: u64,
+}
+
+impl SizeReport {
+    pub fn new(wasm: &[u8]) -> Result<SizeReport> {
+        let mut sections = vec![];
+        let mut imported_functions = 0;
+        let mut function_sizes = vec![];
+        let mut function_names = HashMap::new();
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload.context("Parsing wasm")?;
+
+            match &payload {
+                Payload::ImportSection(imports) => {
+                    for import in imports.clone().into_imports() {
+                        if matches!(import?.ty, wasmparser::TypeRef::Func(_)) {
+                            imported_functions += 1;
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    function_sizes.push(body.range().len() as u64);
+                }
+                Payload::CustomSection(custom) => {
+                    sections.push(SectionSize {
+                        name: custom.name().to_string(),
+                        custom: true,
+                        size: custom.range().len() as u64,
+                    });
+
+                    if let KnownCustom::Name(names) = custom.as_known() {
+                        // A malformed name section only costs us the function names.
+                        for name in names.into_iter().flatten() {
+                            if let Name::Function(map) = name {
+                                for naming in map.into_iter().flatten() {
+                                    function_names.insert(naming.index, naming.name.to_string());
+                                }
+                            }
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some((id, range)) = payload.as_section() {
+                sections.push(SectionSize {
+                    name: section_name(id).to_string(),
+                    custom: false,
+                    size: range.len() as u64,
+                });
+            }
+        }
+
+        let functions = function_sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let index = imported_functions + i as u32;
+                let name = match function_names.get(&index) {
+                    Some(name) => format!("{:#}", rustc_demangle::demangle(name)),
+                    None => format!("func[{index}]"),
+                };
+                FunctionSize { name, size: *size }
+            })
+            .collect::<Vec<_>>();
+
+        let mut crates: HashMap<&str, CrateSize> = HashMap::new();
+        for function in &functions {
+            let name = crate_name(&function.name);
+            let entry = crates.entry(name).or_insert_with(|| CrateSize {
+                name: name.to_string(),
+                functions: 0,
+                size: 0,
+            });
+            entry.functions += 1;
+            entry.size += function.size;
+        }
+        let mut crates = crates.into_values().collect::<Vec<_>>();
+        crates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+        let function_count = functions.len();
+        let mut top_functions = functions;
+        top_functions.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        top_functions.truncate(TOP_FUNCTIONS);
+
+        let sum = |f: &dyn Fn(&SectionSize) -> bool| {
+            sections
+                .iter()
+                .filter(|s| f(s))
+                .map(|s| s.size)
+                .sum::<u64>()
+        };
+
+        Ok(SizeReport {
+            total: wasm.len() as u64,
+            code: sum(&|s| !s.custom && s.name == "code"),
+            data: sum(&|s| !s.custom && s.name == "data"),
+            names: sum(&|s| s.custom && s.name == "name"),
+            debug_info: sum(&|s| s.custom && s.name.starts_with(".debug_")),
+            custom: sum(&|s| s.custom),
+            sections,
+            function_count,
+            top_functions,
+            crates,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Writing {}", path.display()))
+    }
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data_count",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+/// Guesses which crate a demangled function belongs to from the first segment of its path.
+///
+/// For trait impls like `<bevy_ecs::world::World as core::ops::Drop>::drop` this is the crate of
+/// the implementing type, which is usually the one that caused the code to be generated.
+fn crate_name(function: &str) -> &str {
+    let path = function
+        .trim_start_matches(['<', '&', '*'])
+        .trim_start_matches("mut ")
+        .trim_start_matches("const ")
+        .trim_start_matches("dyn ");
+
+    match path.find("::") {
+        Some(end) if !path[..end].contains(['<', ' ', '[', '(']) && end > 0 => &path[..end],
+        _ => "(unknown)",
+    }
+}